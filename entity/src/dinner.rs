@@ -21,18 +21,34 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::dinner_stock::Entity")]
+    DinnerStock,
     #[sea_orm(has_many = "super::extras_dinner::Entity")]
     ExtrasDinner,
+    #[sea_orm(has_many = "super::favourite_dinner::Entity")]
+    FavouriteDinner,
     #[sea_orm(has_many = "super::user_dinner_orders::Entity")]
     UserDinnerOrders,
 }
 
+impl Related<super::dinner_stock::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::DinnerStock.def()
+    }
+}
+
 impl Related<super::extras_dinner::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ExtrasDinner.def()
     }
 }
 
+impl Related<super::favourite_dinner::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FavouriteDinner.def()
+    }
+}
+
 impl Related<super::user_dinner_orders::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::UserDinnerOrders.def()
@@ -10,6 +10,15 @@ pub struct Model {
     pub user_id: i32,
     pub collection_date: DateTimeUtc,
     pub status: u8,
+    #[sea_orm(column_type = "Decimal(Some((8, 2)))")]
+    pub price: Decimal,
+    pub cancelled_by: Option<i32>,
+    pub cancelled_at: Option<DateTimeUtc>,
+    pub cancel_reason: Option<String>,
+    pub pickup_code: Option<String>,
+    pub slot_id: Option<i32>,
+    pub note: Option<String>,
+    pub is_group: i8,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -22,8 +31,22 @@ pub enum Relation {
         on_delete = "Restrict"
     )]
     User,
+    #[sea_orm(has_many = "super::order_history::Entity")]
+    OrderHistory,
+    #[sea_orm(
+        belongs_to = "super::pickup_slot::Entity",
+        from = "Column::SlotId",
+        to = "super::pickup_slot::Column::Id",
+        on_update = "Restrict",
+        on_delete = "Restrict"
+    )]
+    PickupSlot,
+    #[sea_orm(has_many = "super::subscription_day::Entity")]
+    SubscriptionDay,
     #[sea_orm(has_many = "super::user_dinner_orders::Entity")]
     UserDinnerOrders,
+    #[sea_orm(has_many = "super::wallet_transaction::Entity")]
+    WalletTransaction,
 }
 
 impl Related<super::user::Entity> for Entity {
@@ -32,10 +55,34 @@ impl Related<super::user::Entity> for Entity {
     }
 }
 
+impl Related<super::order_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OrderHistory.def()
+    }
+}
+
+impl Related<super::pickup_slot::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PickupSlot.def()
+    }
+}
+
+impl Related<super::subscription_day::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SubscriptionDay.def()
+    }
+}
+
 impl Related<super::user_dinner_orders::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::UserDinnerOrders.def()
     }
 }
 
+impl Related<super::wallet_transaction::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WalletTransaction.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
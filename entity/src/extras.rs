@@ -23,6 +23,10 @@ pub enum Relation {
     ExtrasDinner,
     #[sea_orm(has_many = "super::extras_order::Entity")]
     ExtrasOrder,
+    #[sea_orm(has_many = "super::favourite_extras::Entity")]
+    FavouriteExtras,
+    #[sea_orm(has_many = "super::subscription_extras::Entity")]
+    SubscriptionExtras,
 }
 
 impl Related<super::extras_dinner::Entity> for Entity {
@@ -37,4 +41,16 @@ impl Related<super::extras_order::Entity> for Entity {
     }
 }
 
+impl Related<super::favourite_extras::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FavouriteExtras.def()
+    }
+}
+
+impl Related<super::subscription_extras::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SubscriptionExtras.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -4,13 +4,29 @@ pub mod prelude;
 
 pub mod dinner;
 pub mod dinner_orders;
+pub mod dinner_stock;
 pub mod extras;
 pub mod extras_dinner;
 pub mod extras_order;
+pub mod favourite;
+pub mod favourite_dinner;
+pub mod favourite_extras;
+pub mod idempotency_key;
 pub mod model_enums;
+pub mod order_history;
+pub mod payment_customer;
+pub mod pickup_slot;
 pub mod sea_orm_active_enums;
 pub mod shop;
 pub mod shop_orders;
+pub mod subscription;
+pub mod subscription_day;
+pub mod subscription_extras;
 pub mod user;
 pub mod user_dinner_orders;
+pub mod wallet_account;
+pub mod wallet_entry;
+pub mod wallet_transaction;
+pub mod webhook_event;
 pub mod menu_info;
+pub mod no_show_policy;
@@ -1,4 +1,4 @@
-use sea_orm::{DeriveActiveEnum, EnumIter, strum::FromRepr};
+use sea_orm::{DeriveActiveEnum, EnumIter, Iterable, strum::FromRepr};
 use serde::{Deserialize, Serialize};
 
 #[derive(DeriveActiveEnum, EnumIter, Serialize, Deserialize, Clone)]
@@ -13,7 +13,9 @@ pub enum Weekday {
     Saturday = 5,
 }
 
-#[derive(DeriveActiveEnum, EnumIter, Serialize, Deserialize, Clone, FromRepr)]
+#[derive(
+    DeriveActiveEnum, EnumIter, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, FromRepr,
+)]
 #[sea_orm(rs_type = "u8", db_type = "Integer")]
 #[repr(u8)]
 pub enum Status {
@@ -21,4 +23,106 @@ pub enum Status {
     Prepared = 1,
     Ready = 2,
     Collected = 3,
-}
\ No newline at end of file
+    Cancelled = 4,
+    NoShow = 5,
+}
+
+impl Status {
+    //order lifecycle: Paid -> Prepared -> Ready -> Collected,
+    //paid orders can still be cancelled and anything not collected can end up as a no-show
+    pub fn next(&self) -> &'static [Status] {
+        match self {
+            Status::Paid => &[Status::Prepared, Status::Cancelled, Status::NoShow],
+            Status::Prepared => &[Status::Ready, Status::NoShow],
+            Status::Ready => &[Status::Collected, Status::NoShow],
+            Status::Collected | Status::Cancelled | Status::NoShow => &[],
+        }
+    }
+
+    pub fn can_transition_to(&self, new_status: Status) -> bool {
+        self.next().contains(&new_status)
+    }
+
+    //next step on the happy path towards Collected
+    pub fn forward(&self) -> Option<Status> {
+        match self {
+            Status::Paid => Some(Status::Prepared),
+            Status::Prepared => Some(Status::Ready),
+            Status::Ready => Some(Status::Collected),
+            Status::Collected | Status::Cancelled | Status::NoShow => None,
+        }
+    }
+}
+//owner of a wallet account, only user accounts can't go below 0
+#[derive(
+    DeriveActiveEnum, EnumIter, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, FromRepr,
+)]
+#[sea_orm(rs_type = "u8", db_type = "Integer")]
+#[repr(u8)]
+pub enum AccountKind {
+    User = 0,
+    //money collected by the payment provider
+    Provider = 1,
+    //money spent on orders
+    Sales = 2,
+    //manual corrections made by admins
+    Adjustments = 3,
+}
+
+#[derive(
+    DeriveActiveEnum, EnumIter, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, FromRepr,
+)]
+#[sea_orm(rs_type = "u8", db_type = "Integer")]
+#[repr(u8)]
+pub enum TransactionKind {
+    TopUp = 0,
+    OrderPayment = 1,
+    OrderRefund = 2,
+    //balance moved over from the payment provider's customer
+    Import = 3,
+    Adjustment = 4,
+    //money sent back to the card the top-up was paid with
+    ProviderRefund = 5,
+    //payment disputed by the card holder, taken back by the provider
+    Chargeback = 6,
+}
+
+#[derive(
+    DeriveActiveEnum, EnumIter, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, FromRepr,
+)]
+#[sea_orm(rs_type = "u8", db_type = "Integer")]
+#[repr(u8)]
+pub enum WebhookStatus {
+    //being processed right now
+    Received = 0,
+    Processed = 1,
+    //event type the app doesn't act on
+    Ignored = 2,
+    Failed = 3,
+}
+
+//fixed set of kitchen requests attached to an order line, stored as bits in user_dinner_orders
+#[derive(EnumIter, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u16)]
+pub enum DietaryFlag {
+    GlutenFree = 0,
+    LactoseFree = 1,
+    Vegetarian = 2,
+    Vegan = 3,
+    NutFree = 4,
+    EggFree = 5,
+}
+
+impl DietaryFlag {
+    pub fn bit(&self) -> u16 {
+        1 << (*self as u16)
+    }
+
+    pub fn to_bits(flags: &[DietaryFlag]) -> u16 {
+        flags.iter().fold(0, |bits, flag| bits | flag.bit())
+    }
+
+    pub fn from_bits(bits: u16) -> Vec<DietaryFlag> {
+        Self::iter().filter(|flag| bits & flag.bit() != 0).collect()
+    }
+}
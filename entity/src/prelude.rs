@@ -2,10 +2,26 @@
 
 pub use super::dinner::Entity as Dinner;
 pub use super::dinner_orders::Entity as DinnerOrders;
+pub use super::dinner_stock::Entity as DinnerStock;
 pub use super::extras::Entity as Extras;
 pub use super::extras_dinner::Entity as ExtrasDinner;
 pub use super::extras_order::Entity as ExtrasOrder;
+pub use super::favourite::Entity as Favourite;
+pub use super::favourite_dinner::Entity as FavouriteDinner;
+pub use super::favourite_extras::Entity as FavouriteExtras;
+pub use super::idempotency_key::Entity as IdempotencyKey;
+pub use super::no_show_policy::Entity as NoShowPolicy;
+pub use super::order_history::Entity as OrderHistory;
+pub use super::payment_customer::Entity as PaymentCustomer;
+pub use super::pickup_slot::Entity as PickupSlot;
 pub use super::shop::Entity as Shop;
 pub use super::shop_orders::Entity as ShopOrders;
+pub use super::subscription::Entity as Subscription;
+pub use super::subscription_day::Entity as SubscriptionDay;
+pub use super::subscription_extras::Entity as SubscriptionExtras;
 pub use super::user::Entity as User;
 pub use super::user_dinner_orders::Entity as UserDinnerOrders;
+pub use super::wallet_account::Entity as WalletAccount;
+pub use super::wallet_entry::Entity as WalletEntry;
+pub use super::wallet_transaction::Entity as WalletTransaction;
+pub use super::webhook_event::Entity as WebhookEvent;
@@ -15,12 +15,28 @@ pub struct Model {
     pub verified: i8,
     pub admin: i8,
     pub stripe_id: Option<String>,
+    pub no_shows: i32,
+    pub blocked_until: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_one = "super::dinner_orders::Entity")]
     DinnerOrders,
+    #[sea_orm(has_many = "super::favourite::Entity")]
+    Favourite,
+    #[sea_orm(has_many = "super::subscription::Entity")]
+    Subscription,
+    #[sea_orm(has_many = "super::user_dinner_orders::Entity")]
+    UserDinnerOrders,
+    #[sea_orm(has_many = "super::idempotency_key::Entity")]
+    IdempotencyKey,
+    #[sea_orm(has_many = "super::payment_customer::Entity")]
+    PaymentCustomer,
+    #[sea_orm(has_one = "super::wallet_account::Entity")]
+    WalletAccount,
+    #[sea_orm(has_many = "super::wallet_transaction::Entity")]
+    WalletTransaction,
 }
 
 impl Related<super::dinner_orders::Entity> for Entity {
@@ -29,4 +45,46 @@ impl Related<super::dinner_orders::Entity> for Entity {
     }
 }
 
+impl Related<super::favourite::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Favourite.def()
+    }
+}
+
+impl Related<super::subscription::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Subscription.def()
+    }
+}
+
+impl Related<super::user_dinner_orders::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserDinnerOrders.def()
+    }
+}
+
+impl Related<super::idempotency_key::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::IdempotencyKey.def()
+    }
+}
+
+impl Related<super::payment_customer::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PaymentCustomer.def()
+    }
+}
+
+impl Related<super::wallet_account::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WalletAccount.def()
+    }
+}
+
+impl Related<super::wallet_transaction::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WalletTransaction.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -9,6 +9,12 @@ pub struct Model {
     pub id: i32,
     pub order_id: i32,
     pub dinner_id: i32,
+    pub note: Option<String>,
+    pub dietary_flags: u16,
+    pub attendee_user_id: Option<i32>,
+    pub attendee_name: Option<String>,
+    pub pickup_code: Option<String>,
+    pub collected_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -31,6 +37,14 @@ pub enum Relation {
     DinnerOrders,
     #[sea_orm(has_many = "super::extras_order::Entity")]
     ExtrasOrder,
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::AttendeeUserId",
+        to = "super::user::Column::Id",
+        on_update = "Restrict",
+        on_delete = "SetNull"
+    )]
+    User,
 }
 
 impl Related<super::dinner::Entity> for Entity {
@@ -51,4 +65,10 @@ impl Related<super::extras_order::Entity> for Entity {
     }
 }
 
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -11,6 +11,22 @@ mod m20230324_193952_user_dinner_orders;
 mod m20230324_194709_relations;
 mod m20230324_201744_soup;
 mod m20230402_083722_last_update;
+mod m20230410_171204_dinner_stock;
+mod m20230411_184233_order_price;
+mod m20230413_091512_order_cancellation;
+mod m20230415_120544_order_history;
+mod m20230418_103020_pickup_code;
+mod m20230420_140311_pickup_slots;
+mod m20230423_161022_favourites;
+mod m20230426_190417_subscriptions;
+mod m20230429_113205_order_notes;
+mod m20230502_084517_group_orders;
+mod m20230505_162840_no_shows;
+mod m20230508_093127_idempotency_keys;
+mod m20230511_201504_wallet_ledger;
+mod m20230514_110236_wallet_adjustments;
+mod m20230517_154410_webhook_events;
+mod m20230520_101532_payment_customers;
 
 
 pub struct Migrator;
@@ -30,6 +46,22 @@ impl MigratorTrait for Migrator {
             Box::new(m20230324_194709_relations::Migration),
             Box::new(m20230324_201744_soup::Migration),
             Box::new(m20230402_083722_last_update::Migration),
+            Box::new(m20230410_171204_dinner_stock::Migration),
+            Box::new(m20230411_184233_order_price::Migration),
+            Box::new(m20230413_091512_order_cancellation::Migration),
+            Box::new(m20230415_120544_order_history::Migration),
+            Box::new(m20230418_103020_pickup_code::Migration),
+            Box::new(m20230420_140311_pickup_slots::Migration),
+            Box::new(m20230423_161022_favourites::Migration),
+            Box::new(m20230426_190417_subscriptions::Migration),
+            Box::new(m20230429_113205_order_notes::Migration),
+            Box::new(m20230502_084517_group_orders::Migration),
+            Box::new(m20230505_162840_no_shows::Migration),
+            Box::new(m20230508_093127_idempotency_keys::Migration),
+            Box::new(m20230511_201504_wallet_ledger::Migration),
+            Box::new(m20230514_110236_wallet_adjustments::Migration),
+            Box::new(m20230517_154410_webhook_events::Migration),
+            Box::new(m20230520_101532_payment_customers::Migration),
         ]
     }
 }
@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DinnerStock::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(DinnerStock::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(DinnerStock::DinnerId).integer().not_null())
+                    .col(ColumnDef::new(DinnerStock::Day).date().not_null())
+                    .col(
+                        ColumnDef::new(DinnerStock::Reserved)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_dinnerStock_dinner")
+                            .from_tbl(DinnerStock::Table)
+                            .from_col(DinnerStock::DinnerId)
+                            .to_tbl(Dinner::Table)
+                            .to_col(Dinner::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        //one counter per dish per collection day, reservations upsert into it
+        manager
+            .create_index(
+                Index::create()
+                    .name("unique_dinner_stock_day")
+                    .table(DinnerStock::Table)
+                    .col(DinnerStock::DinnerId)
+                    .col(DinnerStock::Day)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DinnerStock::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum DinnerStock {
+    Table,
+    Id,
+    DinnerId,
+    Day,
+    Reserved,
+}
+
+#[derive(Iden)]
+enum Dinner {
+    Table,
+    Id,
+}
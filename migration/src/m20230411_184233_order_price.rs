@@ -0,0 +1,34 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .add_column(
+                ColumnDef::new(DinnerOrders::Price)
+                    .decimal_len(8, 2)
+                    .not_null()
+                    .default(0),
+            )
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .drop_column(DinnerOrders::Price)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+enum DinnerOrders {
+    Table,
+    Price,
+}
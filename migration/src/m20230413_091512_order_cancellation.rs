@@ -0,0 +1,35 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .add_column(ColumnDef::new(DinnerOrders::CancelledBy).integer().null())
+            .add_column(ColumnDef::new(DinnerOrders::CancelledAt).timestamp().null())
+            .add_column(ColumnDef::new(DinnerOrders::CancelReason).string().null())
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .drop_column(DinnerOrders::CancelledBy)
+            .drop_column(DinnerOrders::CancelledAt)
+            .drop_column(DinnerOrders::CancelReason)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+enum DinnerOrders {
+    Table,
+    CancelledBy,
+    CancelledAt,
+    CancelReason,
+}
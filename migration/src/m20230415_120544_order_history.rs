@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrderHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrderHistory::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(OrderHistory::OrderId).integer().not_null())
+                    //null for the entry created together with the order
+                    .col(
+                        ColumnDef::new(OrderHistory::FromStatus)
+                            .tiny_unsigned()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(OrderHistory::ToStatus)
+                            .tiny_unsigned()
+                            .not_null(),
+                    )
+                    //null when changed by the system, not by a user
+                    .col(ColumnDef::new(OrderHistory::ChangedBy).integer().null())
+                    .col(
+                        ColumnDef::new(OrderHistory::ChangedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(OrderHistory::Note).string().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_orderHistory_DO")
+                            .from_tbl(OrderHistory::Table)
+                            .from_col(OrderHistory::OrderId)
+                            .to_tbl(DinnerOrders::Table)
+                            .to_col(DinnerOrders::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OrderHistory::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum OrderHistory {
+    Table,
+    Id,
+    OrderId,
+    FromStatus,
+    ToStatus,
+    ChangedBy,
+    ChangedAt,
+    Note,
+}
+
+#[derive(Iden)]
+enum DinnerOrders {
+    Table,
+    Id,
+}
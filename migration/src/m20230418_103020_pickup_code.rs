@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .add_column(
+                ColumnDef::new(DinnerOrders::PickupCode)
+                    .string_len(8)
+                    .null(),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        //codes are only unique per collection day, index is for lookups at the counter
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_dinner_orders_pickup_code")
+                    .table(DinnerOrders::Table)
+                    .col(DinnerOrders::PickupCode)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_dinner_orders_pickup_code")
+                    .table(DinnerOrders::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .drop_column(DinnerOrders::PickupCode)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+enum DinnerOrders {
+    Table,
+    PickupCode,
+}
@@ -0,0 +1,114 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PickupSlot::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PickupSlot::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PickupSlot::WeekDay)
+                            .tiny_unsigned()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PickupSlot::StartTime).time().not_null())
+                    .col(ColumnDef::new(PickupSlot::EndTime).time().not_null())
+                    .col(ColumnDef::new(PickupSlot::Capacity).integer().not_null())
+                    //slots referenced by orders are never deleted, only deactivated
+                    .col(
+                        ColumnDef::new(PickupSlot::Active)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .add_column(ColumnDef::new(DinnerOrders::SlotId).integer().null())
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        manager
+            .create_foreign_key(
+                sea_query::ForeignKey::create()
+                    .name("FK_dinnerOrders_pickupSlot")
+                    .from(DinnerOrders::Table, DinnerOrders::SlotId)
+                    .to(PickupSlot::Table, PickupSlot::Id)
+                    .on_delete(ForeignKeyAction::Restrict)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        //one lunch long slot monday - friday so ordering works before admin sets up the real breaks
+        let mut insert = Query::insert();
+        insert.into_table(PickupSlot::Table).columns([
+            PickupSlot::WeekDay,
+            PickupSlot::StartTime,
+            PickupSlot::EndTime,
+            PickupSlot::Capacity,
+        ]);
+        for week_day in 0..5u8 {
+            insert.values_panic([
+                week_day.into(),
+                "11:00:00".into(),
+                "14:00:00".into(),
+                200.into(),
+            ]);
+        }
+        manager.exec_stmt(insert).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_foreign_key(
+                sea_query::ForeignKey::drop()
+                    .name("FK_dinnerOrders_pickupSlot")
+                    .table(DinnerOrders::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .drop_column(DinnerOrders::SlotId)
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        manager
+            .drop_table(Table::drop().table(PickupSlot::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum PickupSlot {
+    Table,
+    Id,
+    WeekDay,
+    StartTime,
+    EndTime,
+    Capacity,
+    Active,
+}
+
+#[derive(Iden)]
+enum DinnerOrders {
+    Table,
+    SlotId,
+}
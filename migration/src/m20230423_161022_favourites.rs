@@ -0,0 +1,185 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Favourite::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Favourite::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Favourite::UserId).integer().not_null())
+                    .col(ColumnDef::new(Favourite::Name).string_len(64).not_null())
+                    .col(ColumnDef::new(Favourite::CreatedAt).timestamp().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_favourite_user")
+                            .from_tbl(Favourite::Table)
+                            .from_col(Favourite::UserId)
+                            .to_tbl(User::Table)
+                            .to_col(User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(FavouriteDinner::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(FavouriteDinner::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(FavouriteDinner::FavouriteId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(FavouriteDinner::DinnerId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_favouriteDinner_favourite")
+                            .from_tbl(FavouriteDinner::Table)
+                            .from_col(FavouriteDinner::FavouriteId)
+                            .to_tbl(Favourite::Table)
+                            .to_col(Favourite::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_favouriteDinner_dinner")
+                            .from_tbl(FavouriteDinner::Table)
+                            .from_col(FavouriteDinner::DinnerId)
+                            .to_tbl(Dinner::Table)
+                            .to_col(Dinner::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(FavouriteExtras::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(FavouriteExtras::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(FavouriteExtras::FavouriteDinnerId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(FavouriteExtras::ExtrasId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_favouriteExtras_favouriteDinner")
+                            .from_tbl(FavouriteExtras::Table)
+                            .from_col(FavouriteExtras::FavouriteDinnerId)
+                            .to_tbl(FavouriteDinner::Table)
+                            .to_col(FavouriteDinner::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_favouriteExtras_extras")
+                            .from_tbl(FavouriteExtras::Table)
+                            .from_col(FavouriteExtras::ExtrasId)
+                            .to_tbl(Extras::Table)
+                            .to_col(Extras::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(FavouriteExtras::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(FavouriteDinner::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Favourite::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Favourite {
+    Table,
+    Id,
+    UserId,
+    Name,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum FavouriteDinner {
+    Table,
+    Id,
+    FavouriteId,
+    DinnerId,
+}
+
+#[derive(Iden)]
+enum FavouriteExtras {
+    Table,
+    Id,
+    FavouriteDinnerId,
+    ExtrasId,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Dinner {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Extras {
+    Table,
+    Id,
+}
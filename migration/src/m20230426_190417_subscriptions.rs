@@ -0,0 +1,231 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Subscription::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Subscription::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Subscription::UserId).integer().not_null())
+                    //bit per menu week_day, 1 = monday, 16 = friday
+                    .col(
+                        ColumnDef::new(Subscription::WeekDays)
+                            .tiny_unsigned()
+                            .not_null(),
+                    )
+                    //slot containing this time is picked on every day
+                    .col(ColumnDef::new(Subscription::PickupTime).time().not_null())
+                    .col(
+                        ColumnDef::new(Subscription::WithSoup)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        ColumnDef::new(Subscription::Active)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        ColumnDef::new(Subscription::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_subscription_user")
+                            .from_tbl(Subscription::Table)
+                            .from_col(Subscription::UserId)
+                            .to_tbl(User::Table)
+                            .to_col(User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(SubscriptionExtras::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SubscriptionExtras::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(SubscriptionExtras::SubscriptionId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SubscriptionExtras::ExtrasId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_subscriptionExtras_subscription")
+                            .from_tbl(SubscriptionExtras::Table)
+                            .from_col(SubscriptionExtras::SubscriptionId)
+                            .to_tbl(Subscription::Table)
+                            .to_col(Subscription::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_subscriptionExtras_extras")
+                            .from_tbl(SubscriptionExtras::Table)
+                            .from_col(SubscriptionExtras::ExtrasId)
+                            .to_tbl(Extras::Table)
+                            .to_col(Extras::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(SubscriptionDay::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SubscriptionDay::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(SubscriptionDay::SubscriptionId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(SubscriptionDay::Day).date().not_null())
+                    //set once the order is placed, stays null when the day failed
+                    .col(ColumnDef::new(SubscriptionDay::OrderId).integer().null())
+                    .col(ColumnDef::new(SubscriptionDay::Failure).string().null())
+                    .col(
+                        ColumnDef::new(SubscriptionDay::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_subscriptionDay_subscription")
+                            .from_tbl(SubscriptionDay::Table)
+                            .from_col(SubscriptionDay::SubscriptionId)
+                            .to_tbl(Subscription::Table)
+                            .to_col(Subscription::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_subscriptionDay_DO")
+                            .from_tbl(SubscriptionDay::Table)
+                            .from_col(SubscriptionDay::OrderId)
+                            .to_tbl(DinnerOrders::Table)
+                            .to_col(DinnerOrders::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        //every subscription is materialised at most once per day
+        manager
+            .create_index(
+                Index::create()
+                    .name("unique_subscription_day")
+                    .table(SubscriptionDay::Table)
+                    .col(SubscriptionDay::SubscriptionId)
+                    .col(SubscriptionDay::Day)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SubscriptionDay::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(SubscriptionExtras::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Subscription::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Subscription {
+    Table,
+    Id,
+    UserId,
+    WeekDays,
+    PickupTime,
+    WithSoup,
+    Active,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum SubscriptionExtras {
+    Table,
+    Id,
+    SubscriptionId,
+    ExtrasId,
+}
+
+#[derive(Iden)]
+enum SubscriptionDay {
+    Table,
+    Id,
+    SubscriptionId,
+    Day,
+    OrderId,
+    Failure,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Extras {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum DinnerOrders {
+    Table,
+    Id,
+}
@@ -0,0 +1,56 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .add_column(ColumnDef::new(DinnerOrders::Note).string().null())
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = sea_query::Table::alter()
+            .table(UserDinnerOrders::Table)
+            .add_column(ColumnDef::new(UserDinnerOrders::Note).string().null())
+            //bit per entity::model_enums::DietaryFlag
+            .add_column(
+                ColumnDef::new(UserDinnerOrders::DietaryFlags)
+                    .small_unsigned()
+                    .not_null()
+                    .default(0),
+            )
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = sea_query::Table::alter()
+            .table(UserDinnerOrders::Table)
+            .drop_column(UserDinnerOrders::Note)
+            .drop_column(UserDinnerOrders::DietaryFlags)
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .drop_column(DinnerOrders::Note)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+enum DinnerOrders {
+    Table,
+    Note,
+}
+
+#[derive(Iden)]
+enum UserDinnerOrders {
+    Table,
+    Note,
+    DietaryFlags,
+}
@@ -0,0 +1,163 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .add_column(
+                ColumnDef::new(DinnerOrders::IsGroup)
+                    .boolean()
+                    .not_null()
+                    .default(false),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = sea_query::Table::alter()
+            .table(UserDinnerOrders::Table)
+            //person the line is meant for in a group order, registered user or just a name
+            .add_column(
+                ColumnDef::new(UserDinnerOrders::AttendeeUserId)
+                    .integer()
+                    .null(),
+            )
+            .add_column(
+                ColumnDef::new(UserDinnerOrders::AttendeeName)
+                    .string_len(64)
+                    .null(),
+            )
+            .add_column(
+                ColumnDef::new(UserDinnerOrders::PickupCode)
+                    .string_len(8)
+                    .null(),
+            )
+            .add_column(
+                ColumnDef::new(UserDinnerOrders::CollectedAt)
+                    .timestamp()
+                    .null(),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        manager
+            .create_foreign_key(
+                sea_query::ForeignKey::create()
+                    .name("FK_UDO_attendee")
+                    .from(UserDinnerOrders::Table, UserDinnerOrders::AttendeeUserId)
+                    .to(User::Table, User::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_user_dinner_orders_pickup_code")
+                    .table(UserDinnerOrders::Table)
+                    .col(UserDinnerOrders::PickupCode)
+                    .to_owned(),
+            )
+            .await?;
+
+        //group orders repeat the same dinner, FK_UDO_DO gets its own index before the unique one goes
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_user_dinner_orders_order")
+                    .table(UserDinnerOrders::Table)
+                    .col(UserDinnerOrders::OrderId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("unique_user_dinner_orders")
+                    .table(UserDinnerOrders::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_index(
+                Index::create()
+                    .name("unique_user_dinner_orders")
+                    .table(UserDinnerOrders::Table)
+                    .col(UserDinnerOrders::OrderId)
+                    .col(UserDinnerOrders::DinnerId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_user_dinner_orders_order")
+                    .table(UserDinnerOrders::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_user_dinner_orders_pickup_code")
+                    .table(UserDinnerOrders::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_foreign_key(
+                sea_query::ForeignKey::drop()
+                    .name("FK_UDO_attendee")
+                    .table(UserDinnerOrders::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        let table = sea_query::Table::alter()
+            .table(UserDinnerOrders::Table)
+            .drop_column(UserDinnerOrders::AttendeeUserId)
+            .drop_column(UserDinnerOrders::AttendeeName)
+            .drop_column(UserDinnerOrders::PickupCode)
+            .drop_column(UserDinnerOrders::CollectedAt)
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = sea_query::Table::alter()
+            .table(DinnerOrders::Table)
+            .drop_column(DinnerOrders::IsGroup)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+enum DinnerOrders {
+    Table,
+    IsGroup,
+}
+
+#[derive(Iden)]
+enum UserDinnerOrders {
+    Table,
+    OrderId,
+    DinnerId,
+    AttendeeUserId,
+    AttendeeName,
+    PickupCode,
+    CollectedAt,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,101 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        //single row, edited by admins
+        manager
+            .create_table(
+                Table::create()
+                    .table(NoShowPolicy::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NoShowPolicy::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    //counted from the end of the order's pickup slot
+                    .col(
+                        ColumnDef::new(NoShowPolicy::ExpireAfterMinutes)
+                            .integer()
+                            .not_null()
+                            .default(60),
+                    )
+                    //0 turns the warning / block off
+                    .col(
+                        ColumnDef::new(NoShowPolicy::WarnAfter)
+                            .integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .col(
+                        ColumnDef::new(NoShowPolicy::BlockAfter)
+                            .integer()
+                            .not_null()
+                            .default(3),
+                    )
+                    .col(
+                        ColumnDef::new(NoShowPolicy::BlockDays)
+                            .integer()
+                            .not_null()
+                            .default(7),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let table = sea_query::Table::alter()
+            .table(User::Table)
+            .add_column(
+                ColumnDef::new(User::NoShows)
+                    .integer()
+                    .not_null()
+                    .default(0),
+            )
+            .add_column(ColumnDef::new(User::BlockedUntil).timestamp().null())
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let mut insert = Query::insert();
+        insert
+            .into_table(NoShowPolicy::Table)
+            .columns([NoShowPolicy::ExpireAfterMinutes])
+            .values_panic([60.into()]);
+        manager.exec_stmt(insert).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = sea_query::Table::alter()
+            .table(User::Table)
+            .drop_column(User::NoShows)
+            .drop_column(User::BlockedUntil)
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        manager
+            .drop_table(Table::drop().table(NoShowPolicy::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum NoShowPolicy {
+    Table,
+    Id,
+    ExpireAfterMinutes,
+    WarnAfter,
+    BlockAfter,
+    BlockDays,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    NoShows,
+    BlockedUntil,
+}
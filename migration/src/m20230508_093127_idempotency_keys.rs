@@ -0,0 +1,95 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(IdempotencyKey::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(IdempotencyKey::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(IdempotencyKey::UserId).integer().not_null())
+                    .col(
+                        ColumnDef::new(IdempotencyKey::Key)
+                            .string_len(64)
+                            .not_null(),
+                    )
+                    //sha256 of path and body, same key with other request is a conflict
+                    .col(
+                        ColumnDef::new(IdempotencyKey::RequestHash)
+                            .string_len(64)
+                            .not_null(),
+                    )
+                    //both null until the first request finishes
+                    .col(
+                        ColumnDef::new(IdempotencyKey::StatusCode)
+                            .small_unsigned()
+                            .null(),
+                    )
+                    .col(ColumnDef::new(IdempotencyKey::ResponseBody).text().null())
+                    .col(
+                        ColumnDef::new(IdempotencyKey::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_idempotencyKey_user")
+                            .from_tbl(IdempotencyKey::Table)
+                            .from_col(IdempotencyKey::UserId)
+                            .to_tbl(User::Table)
+                            .to_col(User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("unique_idempotency_key")
+                    .table(IdempotencyKey::Table)
+                    .col(IdempotencyKey::UserId)
+                    .col(IdempotencyKey::Key)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(IdempotencyKey::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum IdempotencyKey {
+    Table,
+    Id,
+    UserId,
+    Key,
+    RequestHash,
+    StatusCode,
+    ResponseBody,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
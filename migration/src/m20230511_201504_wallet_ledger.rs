@@ -0,0 +1,224 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        //one account per user plus system accounts (user_id null) money moves between
+        manager
+            .create_table(
+                Table::create()
+                    .table(WalletAccount::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WalletAccount::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(WalletAccount::UserId).integer().null())
+                    .col(ColumnDef::new(WalletAccount::Kind).integer().not_null())
+                    //grosze, kept up to date for user accounts only
+                    .col(
+                        ColumnDef::new(WalletAccount::Balance)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(WalletAccount::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_walletAccount_user")
+                            .from_tbl(WalletAccount::Table)
+                            .from_col(WalletAccount::UserId)
+                            .to_tbl(User::Table)
+                            .to_col(User::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("unique_wallet_account_user")
+                    .table(WalletAccount::Table)
+                    .col(WalletAccount::UserId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(WalletTransaction::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WalletTransaction::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(WalletTransaction::Kind).integer().not_null())
+                    .col(ColumnDef::new(WalletTransaction::OrderId).integer().null())
+                    //payment intent id for top-ups, unique so a payment is never booked twice
+                    .col(
+                        ColumnDef::new(WalletTransaction::ExternalRef)
+                            .string_len(255)
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(WalletTransaction::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_walletTransaction_order")
+                            .from_tbl(WalletTransaction::Table)
+                            .from_col(WalletTransaction::OrderId)
+                            .to_tbl(DinnerOrders::Table)
+                            .to_col(DinnerOrders::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("unique_wallet_transaction_ref")
+                    .table(WalletTransaction::Table)
+                    .col(WalletTransaction::ExternalRef)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        //entries are never updated or deleted, amounts of one transaction sum up to 0
+        manager
+            .create_table(
+                Table::create()
+                    .table(WalletEntry::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WalletEntry::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(WalletEntry::TransactionId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(WalletEntry::AccountId).integer().not_null())
+                    .col(ColumnDef::new(WalletEntry::Amount).big_integer().not_null())
+                    //null for system accounts
+                    .col(
+                        ColumnDef::new(WalletEntry::BalanceAfter)
+                            .big_integer()
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_walletEntry_transaction")
+                            .from_tbl(WalletEntry::Table)
+                            .from_col(WalletEntry::TransactionId)
+                            .to_tbl(WalletTransaction::Table)
+                            .to_col(WalletTransaction::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_walletEntry_account")
+                            .from_tbl(WalletEntry::Table)
+                            .from_col(WalletEntry::AccountId)
+                            .to_tbl(WalletAccount::Table)
+                            .to_col(WalletAccount::Id)
+                            .on_delete(ForeignKeyAction::Restrict)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        //provider and sales accounts
+        let mut insert = Query::insert();
+        insert
+            .into_table(WalletAccount::Table)
+            .columns([WalletAccount::Kind, WalletAccount::CreatedAt])
+            .values_panic([1.into(), Expr::current_timestamp().into()])
+            .values_panic([2.into(), Expr::current_timestamp().into()]);
+        manager.exec_stmt(insert).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(WalletEntry::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(WalletTransaction::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(WalletAccount::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum WalletAccount {
+    Table,
+    Id,
+    UserId,
+    Kind,
+    Balance,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum WalletTransaction {
+    Table,
+    Id,
+    Kind,
+    OrderId,
+    ExternalRef,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum WalletEntry {
+    Table,
+    Id,
+    TransactionId,
+    AccountId,
+    Amount,
+    BalanceAfter,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum DinnerOrders {
+    Table,
+    Id,
+}
@@ -0,0 +1,91 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        //reason and author of manual corrections made by admins
+        let table = sea_query::Table::alter()
+            .table(WalletTransaction::Table)
+            .add_column(
+                ColumnDef::new(WalletTransaction::Note)
+                    .string_len(255)
+                    .null(),
+            )
+            .add_column(
+                ColumnDef::new(WalletTransaction::CreatedBy)
+                    .integer()
+                    .null(),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        manager
+            .create_foreign_key(
+                sea_query::ForeignKey::create()
+                    .name("FK_walletTransaction_createdBy")
+                    .from(WalletTransaction::Table, WalletTransaction::CreatedBy)
+                    .to(User::Table, User::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        //counterpart of adjustments
+        let mut insert = Query::insert();
+        insert
+            .into_table(WalletAccount::Table)
+            .columns([WalletAccount::Kind, WalletAccount::CreatedAt])
+            .values_panic([3.into(), Expr::current_timestamp().into()]);
+        manager.exec_stmt(insert).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let mut delete = Query::delete();
+        delete
+            .from_table(WalletAccount::Table)
+            .and_where(Expr::col(WalletAccount::Kind).eq(3))
+            .and_where(Expr::col(WalletAccount::UserId).is_null());
+        manager.exec_stmt(delete).await?;
+
+        manager
+            .drop_foreign_key(
+                sea_query::ForeignKey::drop()
+                    .name("FK_walletTransaction_createdBy")
+                    .table(WalletTransaction::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        let table = sea_query::Table::alter()
+            .table(WalletTransaction::Table)
+            .drop_column(WalletTransaction::Note)
+            .drop_column(WalletTransaction::CreatedBy)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+enum WalletTransaction {
+    Table,
+    Note,
+    CreatedBy,
+}
+
+#[derive(Iden)]
+enum WalletAccount {
+    Table,
+    UserId,
+    Kind,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,138 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        //every delivery the payment provider sent, kept so retries are processed once
+        //and failed ones can be replayed
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebhookEvent::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WebhookEvent::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(WebhookEvent::EventId)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(WebhookEvent::EventType)
+                            .string_len(64)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(WebhookEvent::Payload).text().not_null())
+                    .col(ColumnDef::new(WebhookEvent::Status).integer().not_null())
+                    .col(
+                        ColumnDef::new(WebhookEvent::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(WebhookEvent::Error).string_len(255).null())
+                    .col(
+                        ColumnDef::new(WebhookEvent::ReceivedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(WebhookEvent::ProcessedAt).timestamp().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("unique_webhook_event_id")
+                    .table(WebhookEvent::Table)
+                    .col(WebhookEvent::EventId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        //payment intent a top-up, refund or chargeback belongs to
+        let table = sea_query::Table::alter()
+            .table(WalletTransaction::Table)
+            .add_column(
+                ColumnDef::new(WalletTransaction::PaymentRef)
+                    .string_len(255)
+                    .null(),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_wallet_transaction_payment_ref")
+                    .table(WalletTransaction::Table)
+                    .col(WalletTransaction::PaymentRef)
+                    .to_owned(),
+            )
+            .await?;
+
+        //top-ups so far kept the intent id only as their external_ref
+        let mut update = Query::update();
+        update
+            .table(WalletTransaction::Table)
+            .value(
+                WalletTransaction::PaymentRef,
+                Expr::col(WalletTransaction::ExternalRef),
+            )
+            .and_where(Expr::col(WalletTransaction::Kind).eq(0));
+        manager.exec_stmt(update).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_wallet_transaction_payment_ref")
+                    .table(WalletTransaction::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        let table = sea_query::Table::alter()
+            .table(WalletTransaction::Table)
+            .drop_column(WalletTransaction::PaymentRef)
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        manager
+            .drop_table(Table::drop().table(WebhookEvent::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum WebhookEvent {
+    Table,
+    Id,
+    EventId,
+    EventType,
+    Payload,
+    Status,
+    Attempts,
+    Error,
+    ReceivedAt,
+    ProcessedAt,
+}
+
+#[derive(Iden)]
+enum WalletTransaction {
+    Table,
+    Kind,
+    ExternalRef,
+    PaymentRef,
+}
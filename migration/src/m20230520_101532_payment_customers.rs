@@ -0,0 +1,115 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        //every customer a user ever had at the payment provider. Deleted wallets stay here
+        //so late webhooks of their payments still find the user
+        manager
+            .create_table(
+                Table::create()
+                    .table(PaymentCustomer::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PaymentCustomer::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PaymentCustomer::CustomerId)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PaymentCustomer::UserId).integer().not_null())
+                    .col(
+                        ColumnDef::new(PaymentCustomer::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PaymentCustomer::DeletedAt)
+                            .timestamp()
+                            .null(),
+                    )
+                    //deleted at the provider too, which happens after the wallet is gone
+                    .col(
+                        ColumnDef::new(PaymentCustomer::ProviderDeletedAt)
+                            .timestamp()
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_paymentCustomer_user")
+                            .from_tbl(PaymentCustomer::Table)
+                            .from_col(PaymentCustomer::UserId)
+                            .to_tbl(User::Table)
+                            .to_col(User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("unique_payment_customer_id")
+                    .table(PaymentCustomer::Table)
+                    .col(PaymentCustomer::CustomerId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        //wallets initialized so far only have their customer in user.stripe_id
+        let mut insert = Query::insert();
+        insert
+            .into_table(PaymentCustomer::Table)
+            .columns([
+                PaymentCustomer::CustomerId,
+                PaymentCustomer::UserId,
+                PaymentCustomer::CreatedAt,
+            ])
+            .select_from(
+                Query::select()
+                    .column(User::StripeId)
+                    .column(User::Id)
+                    .expr(Expr::current_timestamp())
+                    .from(User::Table)
+                    .and_where(Expr::col(User::StripeId).is_not_null())
+                    .to_owned(),
+            )
+            .map_err(|e| DbErr::Migration(e.to_string()))?;
+        manager.exec_stmt(insert).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PaymentCustomer::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum PaymentCustomer {
+    Table,
+    Id,
+    CustomerId,
+    UserId,
+    CreatedAt,
+    DeletedAt,
+    ProviderDeletedAt,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+    StripeId,
+}
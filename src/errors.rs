@@ -21,6 +21,9 @@ pub enum ServiceError {
     #[display(fmt = "{}", _0)]
     NotFound(String),
 
+    #[display(fmt = "{}", _0)]
+    Conflict(String),
+
     #[display(fmt = "Invalid {} Token", _0)]
     JWTInvalidToken(String),
 
@@ -43,6 +46,7 @@ impl ResponseError for ServiceError {
             ServiceError::JWTInvalidToken(_) => StatusCode::UNAUTHORIZED,
             ServiceError::JWTExpiredToken(_) => StatusCode::UNAUTHORIZED,
             ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServiceError::Conflict(_) => StatusCode::CONFLICT,
         }
     }
 }
@@ -0,0 +1,117 @@
+use std::{fmt, time::Duration};
+
+use actix_web::web::Bytes;
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use entity::model_enums::Status;
+use futures_util::{stream, Stream};
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::routes::structs::DinnerResponse;
+
+//how long an idle SSE connection waits before sending a keep-alive comment
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum OrderEvent {
+    #[serde(rename_all = "camelCase")]
+    OrderCreated {
+        order_id: i32,
+        user_id: i32,
+        #[serde(with = "ts_seconds")]
+        collection_date: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        dinners: Vec<DinnerResponse>,
+    },
+    //lines of a paid order were replaced by its owner
+    #[serde(rename_all = "camelCase")]
+    OrderModified {
+        order_id: i32,
+        user_id: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        dinners: Vec<DinnerResponse>,
+    },
+    #[serde(rename_all = "camelCase")]
+    StatusChanged {
+        order_id: i32,
+        user_id: i32,
+        from: Status,
+        to: Status,
+    },
+}
+
+impl OrderEvent {
+    pub fn user_id(&self) -> i32 {
+        match self {
+            Self::OrderCreated { user_id, .. } => *user_id,
+            Self::OrderModified { user_id, .. } => *user_id,
+            Self::StatusChanged { user_id, .. } => *user_id,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::OrderCreated { .. } => "orderCreated",
+            Self::OrderModified { .. } => "orderModified",
+            Self::StatusChanged { .. } => "statusChanged",
+        }
+    }
+}
+
+//in-process pub/sub for order changes, handlers publish after their transaction commits
+#[derive(Clone)]
+pub struct EventBus(broadcast::Sender<OrderEvent>);
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Event bus")
+            .field("subscribers", &self.0.receiver_count())
+            .finish()
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self(sender)
+    }
+
+    pub fn publish(&self, event: OrderEvent) {
+        //no one listening is not an error
+        let _ = self.0.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OrderEvent> {
+        self.0.subscribe()
+    }
+}
+
+//Turns subscription into text/event-stream body, events not passing `filter` are skipped
+pub fn sse_stream<F>(
+    receiver: broadcast::Receiver<OrderEvent>,
+    filter: F,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>>
+where
+    F: Fn(&OrderEvent) -> bool + 'static,
+{
+    stream::unfold((receiver, filter), |(mut receiver, filter)| async move {
+        loop {
+            let chunk = match tokio::time::timeout(KEEP_ALIVE, receiver.recv()).await {
+                Err(_) => Bytes::from_static(b": keep-alive\n\n"),
+                Ok(Ok(event)) if filter(&event) => {
+                    let data = serde_json::to_string(&event).unwrap();
+                    Bytes::from(format!("event: {}\ndata: {}\n\n", event.name(), data))
+                }
+                Ok(Ok(_)) => continue,
+                //slow client missed some events, tell it to refetch the lists
+                Ok(Err(RecvError::Lagged(_))) => Bytes::from_static(b"event: lagged\ndata: {}\n\n"),
+                Ok(Err(RecvError::Closed)) => return None,
+            };
+
+            return Some((Ok(chunk), (receiver, filter)));
+        }
+    })
+}
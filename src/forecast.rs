@@ -0,0 +1,299 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use entity::{dinner, dinner_orders, extras, extras_order, pickup_slot, user_dinner_orders};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, EntityTrait, JoinType, QueryFilter, QuerySelect, RelationTrait,
+};
+
+use crate::{
+    csv_field,
+    errors::ServiceError,
+    map_db_err,
+    routes::{
+        order::PENDING_STATUSES,
+        structs::{ForecastDay, ForecastItem, ForecastReport, ForecastSlot},
+    },
+    stock::{collection_day, day_bounds},
+};
+
+//portions of every dinner and extra keyed by their id
+#[derive(Default)]
+struct Counts {
+    dinners: HashMap<i32, i64>,
+    extras: HashMap<i32, i64>,
+}
+
+//Portions still to be handed out on every day between `from` and `to`.
+//Only open orders count, cancelled, collected and no-show ones need no cooking and neither
+//do lines of a group order that were already handed out
+pub async fn forecast<C>(
+    conn: &C,
+    from: NaiveDate,
+    to: NaiveDate,
+    by_slot: bool,
+) -> Result<ForecastReport, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let (start, _) = day_bounds(from);
+    let (_, end) = day_bounds(to);
+
+    //collection_date is the slot's start so it's the same for every order of given slot and day
+    let dinner_counts: Vec<(DateTime<Utc>, Option<i32>, i32, i64)> =
+        user_dinner_orders::Entity::find()
+            .select_only()
+            .column(dinner_orders::Column::CollectionDate)
+            .column(dinner_orders::Column::SlotId)
+            .column(user_dinner_orders::Column::DinnerId)
+            .column_as(user_dinner_orders::Column::Id.count(), "portions")
+            .join(
+                JoinType::InnerJoin,
+                user_dinner_orders::Relation::DinnerOrders.def(),
+            )
+            .filter(dinner_orders::Column::Status.is_in(PENDING_STATUSES))
+            .filter(user_dinner_orders::Column::CollectedAt.is_null())
+            .filter(dinner_orders::Column::CollectionDate.gte(start))
+            .filter(dinner_orders::Column::CollectionDate.lt(end))
+            .group_by(dinner_orders::Column::CollectionDate)
+            .group_by(dinner_orders::Column::SlotId)
+            .group_by(user_dinner_orders::Column::DinnerId)
+            .into_tuple()
+            .all(conn)
+            .await
+            .map_err(map_db_err)?;
+    let extras_counts: Vec<(DateTime<Utc>, Option<i32>, i32, i64)> = extras_order::Entity::find()
+        .select_only()
+        .column(dinner_orders::Column::CollectionDate)
+        .column(dinner_orders::Column::SlotId)
+        .column(extras_order::Column::ExtrasId)
+        .column_as(extras_order::Column::Id.count(), "portions")
+        .join(
+            JoinType::InnerJoin,
+            extras_order::Relation::UserDinnerOrders.def(),
+        )
+        .join(
+            JoinType::InnerJoin,
+            user_dinner_orders::Relation::DinnerOrders.def(),
+        )
+        .filter(dinner_orders::Column::Status.is_in(PENDING_STATUSES))
+        .filter(user_dinner_orders::Column::CollectedAt.is_null())
+        .filter(dinner_orders::Column::CollectionDate.gte(start))
+        .filter(dinner_orders::Column::CollectionDate.lt(end))
+        .group_by(dinner_orders::Column::CollectionDate)
+        .group_by(dinner_orders::Column::SlotId)
+        .group_by(extras_order::Column::ExtrasId)
+        .into_tuple()
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+
+    let dinners: HashMap<_, _> = dinner::Entity::find()
+        .filter(dinner::Column::Id.is_in(dinner_counts.iter().map(|c| c.2)))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|dinner| (dinner.id, dinner.name))
+        .collect();
+    let extras: HashMap<_, _> = extras::Entity::find()
+        .filter(extras::Column::Id.is_in(extras_counts.iter().map(|c| c.2)))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|extra| (extra.id, extra.name))
+        .collect();
+    let slots: HashMap<_, _> = pickup_slot::Entity::find()
+        .filter(
+            pickup_slot::Column::Id.is_in(
+                dinner_counts
+                    .iter()
+                    .chain(extras_counts.iter())
+                    .filter_map(|c| c.1),
+            ),
+        )
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|slot| (slot.id, slot))
+        .collect();
+
+    let mut days: BTreeMap<NaiveDate, Counts> = BTreeMap::new();
+    let mut day_slots: BTreeMap<(NaiveDate, Option<i32>), Counts> = BTreeMap::new();
+    for (collection_date, slot_id, dinner_id, portions) in dinner_counts {
+        let day = collection_day(&collection_date);
+        *days
+            .entry(day)
+            .or_default()
+            .dinners
+            .entry(dinner_id)
+            .or_default() += portions;
+        *day_slots
+            .entry((day, slot_id))
+            .or_default()
+            .dinners
+            .entry(dinner_id)
+            .or_default() += portions;
+    }
+    for (collection_date, slot_id, extras_id, portions) in extras_counts {
+        let day = collection_day(&collection_date);
+        *days
+            .entry(day)
+            .or_default()
+            .extras
+            .entry(extras_id)
+            .or_default() += portions;
+        *day_slots
+            .entry((day, slot_id))
+            .or_default()
+            .extras
+            .entry(extras_id)
+            .or_default() += portions;
+    }
+
+    let items = |counts: &HashMap<i32, i64>, names: &HashMap<i32, String>| {
+        let mut items = counts
+            .iter()
+            .map(|(id, portions)| ForecastItem {
+                id: *id,
+                name: names.get(id).cloned().unwrap_or_default(),
+                portions: *portions,
+            })
+            .collect::<Vec<_>>();
+        //most portions first, that's what the kitchen starts with
+        items.sort_by(|a, b| b.portions.cmp(&a.portions).then(a.name.cmp(&b.name)));
+        items
+    };
+
+    let days = days
+        .into_iter()
+        .map(|(day, counts)| {
+            let mut slot_list = Vec::new();
+            if by_slot {
+                for ((_, slot_id), counts) in day_slots.range((day, None)..=(day, Some(i32::MAX))) {
+                    let slot = slot_id.and_then(|id| slots.get(&id));
+                    slot_list.push(ForecastSlot {
+                        slot_id: *slot_id,
+                        start_time: slot.map(|s| s.start_time),
+                        end_time: slot.map(|s| s.end_time),
+                        dinners: items(&counts.dinners, &dinners),
+                        extras: items(&counts.extras, &extras),
+                    });
+                }
+                slot_list.sort_by_key(|s| (s.start_time.is_none(), s.start_time));
+            }
+
+            ForecastDay {
+                day,
+                dinners: items(&counts.dinners, &dinners),
+                extras: items(&counts.extras, &extras),
+                slots: slot_list,
+            }
+        })
+        .collect();
+
+    Ok(ForecastReport {
+        from,
+        to,
+        by_slot,
+        days,
+    })
+}
+
+//one row per day (and slot), dinner or extra
+pub fn forecast_csv(report: &ForecastReport) -> String {
+    let mut csv = String::from("day,slot,kind,id,name,portions\n");
+    for day in report.days.iter() {
+        let mut push_rows = |slot: &str, dinners: &[ForecastItem], extras: &[ForecastItem]| {
+            let rows = dinners
+                .iter()
+                .map(|item| ("dinner", item))
+                .chain(extras.iter().map(|item| ("extra", item)));
+            for (kind, item) in rows {
+                csv.push_str(&format!(
+                    "{},{},{},{},{},{}\n",
+                    day.day,
+                    csv_field(slot),
+                    kind,
+                    item.id,
+                    csv_field(&item.name),
+                    item.portions
+                ));
+            }
+        };
+
+        if day.slots.is_empty() {
+            push_rows("", &day.dinners, &day.extras);
+        }
+        for slot in day.slots.iter() {
+            push_rows(&slot_label(slot), &slot.dinners, &slot.extras);
+        }
+    }
+    csv
+}
+
+fn slot_label(slot: &ForecastSlot) -> String {
+    match (slot.start_time, slot.end_time) {
+        (Some(start), Some(end)) => format!("{}-{}", start.format("%H:%M"), end.format("%H:%M")),
+        _ => "no slot".into(),
+    }
+}
+
+//printable prep sheet, one page per day
+pub fn forecast_html(report: &ForecastReport) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Prep sheet</title>\n\
+         <style>\n\
+         body { font-family: sans-serif; }\n\
+         section { page-break-after: always; }\n\
+         table { border-collapse: collapse; margin-bottom: 1em; }\n\
+         td, th { border: 1px solid #444; padding: 4px 8px; text-align: left; }\n\
+         td.portions { text-align: right; font-weight: bold; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+
+    if report.days.is_empty() {
+        html.push_str(&format!(
+            "<p>No orders between {} and {}</p>\n",
+            report.from, report.to
+        ));
+    }
+    for day in report.days.iter() {
+        html.push_str(&format!(
+            "<section>\n<h1>{}</h1>\n",
+            day.day.format("%A %Y-%m-%d")
+        ));
+        html.push_str(&items_table(&day.dinners, &day.extras));
+        for slot in day.slots.iter() {
+            html.push_str(&format!("<h2>{}</h2>\n", escape_html(&slot_label(slot))));
+            html.push_str(&items_table(&slot.dinners, &slot.extras));
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn items_table(dinners: &[ForecastItem], extras: &[ForecastItem]) -> String {
+    let mut table = String::from("<table>\n<tr><th>Portions</th><th>Dish</th></tr>\n");
+    for item in dinners.iter().chain(extras.iter()) {
+        table.push_str(&format!(
+            "<tr><td class=\"portions\">{}</td><td>{}</td></tr>\n",
+            item.portions,
+            escape_html(&item.name)
+        ));
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,227 @@
+use std::future::Future;
+
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    FromRequest, HttpResponse,
+};
+use chrono::{DateTime, Duration, Utc};
+use entity::idempotency_key;
+use log::error;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{convert_err_to_500, errors::ServiceError, map_db_err};
+
+pub const IDEMPOTENCY_HEADER: &str = "Idempotency-Key";
+const KEY_MAX_LEN: usize = 64;
+//A claim still without a response after this long belongs to a request that died halfway,
+//e.g. with the server restarting. What it managed to do is unknown, so retries with its key
+//are told to start over with a new one instead of running it again
+const CLAIM_TIMEOUT_MINUTES: i64 = 10;
+
+//how long a stored response is replayed, configured in hours as IDEMPOTENCY_TTL_HOURS
+fn ttl() -> Duration {
+    let hours = dotenvy::var("IDEMPOTENCY_TTL_HOURS")
+        .ok()
+        .and_then(|h| h.parse::<i64>().ok())
+        .unwrap_or(24);
+    Duration::hours(hours)
+}
+
+fn expired(created_at: &DateTime<Utc>) -> bool {
+    *created_at < Utc::now() - ttl()
+}
+
+fn abandoned(stored: &idempotency_key::Model) -> bool {
+    stored.status_code.is_none()
+        && stored.created_at < Utc::now() - Duration::minutes(CLAIM_TIMEOUT_MINUTES)
+}
+
+//optional `Idempotency-Key` header together with the path it was sent to
+pub struct Idempotency {
+    key: Option<String>,
+    path: String,
+    body: Vec<u8>,
+}
+
+impl FromRequest for Idempotency {
+    type Error = ServiceError;
+    type Future = std::future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let key = match req.headers().get(IDEMPOTENCY_HEADER) {
+            None => None,
+            Some(value) => match value.to_str().map(str::trim) {
+                Ok(key) if !key.is_empty() && key.len() <= KEY_MAX_LEN => Some(key.to_string()),
+                _ => {
+                    return std::future::ready(Err(ServiceError::BadRequest(format!(
+                        "{} has to be between 1 and {} ASCII characters",
+                        IDEMPOTENCY_HEADER, KEY_MAX_LEN
+                    ))))
+                }
+            },
+        };
+
+        std::future::ready(Ok(Idempotency {
+            key,
+            path: req.path().to_string(),
+            body: Vec::new(),
+        }))
+    }
+}
+
+impl Idempotency {
+    //parsed request body, hashed with the path to spot a key reused for something else
+    pub fn with_body(mut self, body: &impl Serialize) -> Result<Self, ServiceError> {
+        self.body = serde_json::to_vec(body)
+            .map_err(|e| convert_err_to_500(e, Some("Request serialization err")))?;
+        Ok(self)
+    }
+
+    //Runs `handler` once per user and key, retries with the same key get the first response back.
+    //Failed requests aren't stored so they can be retried with the same key
+    pub async fn respond<T, F>(
+        self,
+        conn: &DatabaseConnection,
+        user_id: i32,
+        handler: F,
+    ) -> Result<HttpResponse, ServiceError>
+    where
+        T: Serialize,
+        F: Future<Output = Result<T, ServiceError>>,
+    {
+        let request_hash = self.request_hash();
+        let Some(key) = self.key else {
+            return handler.await.map(|res| HttpResponse::Ok().json(res));
+        };
+
+        let stored = idempotency_key::Entity::find()
+            .filter(idempotency_key::Column::UserId.eq(user_id))
+            .filter(idempotency_key::Column::Key.eq(key.as_str()))
+            .one(conn)
+            .await
+            .map_err(map_db_err)?;
+        match stored {
+            Some(stored) if expired(&stored.created_at) => {
+                idempotency_key::Entity::delete_by_id(stored.id)
+                    .exec(conn)
+                    .await
+                    .map_err(map_db_err)?;
+            }
+            Some(stored) => return replay(stored, &request_hash),
+            None => {}
+        }
+
+        //unique (user_id, key) index lets only one of concurrent retries through
+        let claim = idempotency_key::ActiveModel {
+            user_id: Set(user_id),
+            key: Set(key.clone()),
+            request_hash: Set(request_hash),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await;
+        let claim = match claim {
+            Ok(claim) => claim,
+            Err(err) => {
+                let taken = idempotency_key::Entity::find()
+                    .filter(idempotency_key::Column::UserId.eq(user_id))
+                    .filter(idempotency_key::Column::Key.eq(key))
+                    .one(conn)
+                    .await
+                    .map_err(map_db_err)?;
+                return Err(match taken {
+                    Some(_) => in_progress(),
+                    None => map_db_err(err),
+                });
+            }
+        };
+
+        let result = match handler.await {
+            Ok(res) => serde_json::to_string(&res)
+                .map_err(|e| convert_err_to_500(e, Some("Response serialization err"))),
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(body) => {
+                let mut claim: idempotency_key::ActiveModel = claim.into();
+                claim.status_code = Set(Some(StatusCode::OK.as_u16()));
+                claim.response_body = Set(Some(body.clone()));
+                //the request itself succeeded, a retry would at worst repeat it
+                if let Err(err) = claim.update(conn).await {
+                    error!("Storing idempotent response failed: {}", err);
+                }
+                Ok(HttpResponse::Ok()
+                    .content_type(ContentType::json())
+                    .body(body))
+            }
+            Err(err) => {
+                idempotency_key::Entity::delete_by_id(claim.id)
+                    .exec(conn)
+                    .await
+                    .map_err(map_db_err)?;
+                Err(err)
+            }
+        }
+    }
+
+    fn request_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.path.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(&self.body);
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+fn in_progress() -> ServiceError {
+    ServiceError::Conflict(format!(
+        "A request with this {} is still being processed",
+        IDEMPOTENCY_HEADER
+    ))
+}
+
+fn replay(
+    stored: idempotency_key::Model,
+    request_hash: &str,
+) -> Result<HttpResponse, ServiceError> {
+    if stored.request_hash != request_hash {
+        return Err(ServiceError::Conflict(format!(
+            "{} was already used for a different request",
+            IDEMPOTENCY_HEADER
+        )));
+    }
+    let interrupted = abandoned(&stored);
+    let (Some(status), Some(body)) = (stored.status_code, stored.response_body) else {
+        return Err(if interrupted {
+            ServiceError::Conflict(format!(
+                "Request with this {} was interrupted, check its outcome and retry with a new key",
+                IDEMPOTENCY_HEADER
+            ))
+        } else {
+            in_progress()
+        });
+    };
+
+    let status = StatusCode::from_u16(status).map_err(|_| ServiceError::InternalError)?;
+    Ok(HttpResponse::build(status)
+        .content_type(ContentType::json())
+        .insert_header(("Idempotent-Replayed", "true"))
+        .body(body))
+}
+
+//drops keys past their window, run from the background jobs
+pub async fn purge_expired(conn: &DatabaseConnection) -> Result<(), ServiceError> {
+    idempotency_key::Entity::delete_many()
+        .filter(idempotency_key::Column::CreatedAt.lt(Utc::now() - ttl()))
+        .exec(conn)
+        .await
+        .map_err(map_db_err)?;
+    Ok(())
+}
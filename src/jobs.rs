@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use actix_web::web;
+use log::error;
+
+use crate::{
+    appstate::AppState,
+    idempotency::purge_expired,
+    no_shows::expire_orders,
+    subscriptions::materialise_subscriptions,
+    wallet::{reconcile_pending_refunds, retry_customer_deletions},
+};
+
+//subscriptions are retried every hour so new subscribers get this week's remaining days,
+//old idempotency keys are dropped, stuck card refunds reconciled and customers of deleted
+//wallets removed at the provider on the same tick
+const SUBSCRIPTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+//uncollected orders are checked often enough to be closed soon after their slot ends
+const NO_SHOW_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+//background work running for the whole lifetime of the server
+pub fn spawn_jobs(state: web::Data<AppState>) {
+    let no_show_state = state.clone();
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(NO_SHOW_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = expire_orders(&no_show_state).await {
+                error!("No-show job failed: {}", err);
+            }
+        }
+    });
+
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(SUBSCRIPTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = materialise_subscriptions(&state).await {
+                error!("Subscription job failed: {}", err);
+            }
+            if let Err(err) = purge_expired(&state.conn).await {
+                error!("Idempotency key cleanup failed: {}", err);
+            }
+            if let Err(err) = reconcile_pending_refunds(&state.conn, state.payments.as_ref()).await
+            {
+                error!("Pending refund reconciliation failed: {}", err);
+            }
+            if let Err(err) = retry_customer_deletions(&state.conn, state.payments.as_ref()).await {
+                error!("Customer deletion retry failed: {}", err);
+            }
+        }
+    });
+}
+
+//one off run, e.g. right after a new menu is stored
+pub fn run_subscriptions(state: web::Data<AppState>) {
+    actix_rt::spawn(async move {
+        if let Err(err) = materialise_subscriptions(&state).await {
+            error!("Subscription job failed: {}", err);
+        }
+    });
+}
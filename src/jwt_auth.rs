@@ -1,9 +1,9 @@
 use actix_web::http::header;
 use actix_web::FromRequest;
-use chrono::Utc;
+use chrono::{Local, NaiveDate, NaiveTime, Utc};
 use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::errors::ServiceError;
 
@@ -62,8 +62,20 @@ impl FromRequest for AuthUser {
     }
 }
 
+//Every token is signed with the same secret, the kind stops one being used as another
+//(e.g. a QR payload whose sub is an order id as a refresh token for the user with that id)
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum TokenType {
+    Access,
+    Refresh,
+    Pickup,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct AccessTokenClaims {
+    typ: TokenType,
     sub: String,
     username: String,
     email: String,
@@ -82,6 +94,7 @@ impl AccessTokenClaims {
         exp_seconds: i64,
     ) -> Self {
         Self {
+            typ: TokenType::Access,
             sub: id.to_string(),
             username: username.to_string(),
             email: email.to_string(),
@@ -93,33 +106,29 @@ impl AccessTokenClaims {
 }
 
 fn decode_access_token(token: String) -> Result<AuthUser, ServiceError> {
-    let binding = dotenvy::var("JWT_SECRET").expect("NO JWT_SECRET val provided in .env");
-    let secret = binding.as_bytes();
-    
-    let decoded = decode::<AccessTokenClaims>(
-        &token,
-        &DecodingKey::from_secret(secret),
-        &Validation::default(),
-    )
-    .map_err(|err| map_decode_err(err, "Access"))?;
+    let claims = decode_claims::<AccessTokenClaims>(&token, "Access")?;
+    if claims.typ != TokenType::Access {
+        return Err(ServiceError::JWTInvalidToken("Access".to_string()));
+    }
 
-    let uid = decoded
-        .claims
+    let uid = claims
         .sub
         .parse::<i32>()
         .map_err(|_| ServiceError::JWTInvalidToken("Access".to_string()))?;
 
     Ok(AuthUser {
         id: uid,
-        is_admin: decoded.claims.is_admin,
-        username: decoded.claims.username,
-        email: decoded.claims.email,
-        is_verified: decoded.claims.is_verified,
+        is_admin: claims.is_admin,
+        username: claims.username,
+        email: claims.email,
+        is_verified: claims.is_verified,
     })
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RefreshTokenClaims {
+    typ: TokenType,
     sub: String,
     exp: usize,
 }
@@ -127,6 +136,7 @@ pub struct RefreshTokenClaims {
 impl RefreshTokenClaims {
     pub fn new(id: i32, exp_seconds: i64) -> Self {
         Self {
+            typ: TokenType::Refresh,
             sub: id.to_string(),
             exp: get_expiration(exp_seconds),
         }
@@ -134,23 +144,82 @@ impl RefreshTokenClaims {
 }
 
 pub fn decode_refresh_token(token: &str) -> Result<i32, ServiceError> {
+    let claims = decode_claims::<RefreshTokenClaims>(token, "Refresh")?;
+    if claims.typ != TokenType::Refresh {
+        return Err(ServiceError::JWTInvalidToken("Refresh".to_string()));
+    }
+
+    let uid = claims
+        .sub
+        .parse::<i32>()
+        .map_err(|_| ServiceError::JWTInvalidToken("Refresh".to_string()))?;
+
+    Ok(uid)
+}
+
+//signed content of the QR code shown at the counter
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PickupClaims {
+    typ: TokenType,
+    sub: String,
+    pub code: String,
+    pub day: NaiveDate,
+    exp: usize,
+}
+
+impl PickupClaims {
+    pub fn new(order_id: i32, code: &str, day: NaiveDate) -> Self {
+        //valid until the end of collection day
+        let exp = day
+            .succ_opt()
+            .expect("valid date")
+            .and_time(NaiveTime::MIN)
+            .and_local_timezone(Local)
+            .earliest()
+            .expect("valid timestamp")
+            .timestamp() as usize;
+
+        Self {
+            typ: TokenType::Pickup,
+            sub: order_id.to_string(),
+            code: code.to_string(),
+            day,
+            exp,
+        }
+    }
+
+    pub fn order_id(&self) -> Result<i32, ServiceError> {
+        self.sub
+            .parse::<i32>()
+            .map_err(|_| ServiceError::JWTInvalidToken("Pickup".to_string()))
+    }
+}
+
+pub fn decode_pickup_token(token: &str) -> Result<PickupClaims, ServiceError> {
+    let claims = decode_claims::<PickupClaims>(token, "Pickup")?;
+    if claims.typ != TokenType::Pickup {
+        return Err(ServiceError::JWTInvalidToken("Pickup".to_string()));
+    }
+
+    Ok(claims)
+}
+
+fn decode_claims<T>(token: &str, token_name: &str) -> Result<T, ServiceError>
+where
+    T: DeserializeOwned,
+{
     let binding = dotenvy::var("JWT_SECRET").expect("NO JWT_SECRET val provided in .env");
     let secret = binding.as_bytes();
 
-    let decoded = decode::<RefreshTokenClaims>(
+    let decoded = decode::<T>(
         token,
         &DecodingKey::from_secret(secret),
         &Validation::default(),
     )
-    .map_err(|err| map_decode_err(err, "Refresh"))?;
+    .map_err(|err| map_decode_err(err, token_name))?;
 
-    let uid = decoded
-        .claims
-        .sub
-        .parse::<i32>()
-        .map_err(|_| ServiceError::JWTInvalidToken("Refresh".to_string()))?;
-
-    Ok(uid)
+    Ok(decoded.claims)
 }
 
 pub fn get_expiration(seconds: i64) -> usize {
@@ -181,3 +250,38 @@ pub fn map_decode_err(err: JwtError, token_name: &str) -> ServiceError {
 
     ServiceError::JWTInvalidToken(token_name.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_secret() {
+        std::env::set_var("JWT_SECRET", "test secret");
+    }
+
+    #[test]
+    fn pickup_payload_is_not_a_session_token() {
+        set_secret();
+        let day = Local::now().date_naive();
+        let payload = encode_jwt(&PickupClaims::new(1, "123456", day)).unwrap();
+
+        assert!(decode_pickup_token(&payload).is_ok());
+        assert!(decode_refresh_token(&payload).is_err());
+        assert!(decode_access_token(payload).is_err());
+    }
+
+    #[test]
+    fn session_tokens_are_not_interchangeable() {
+        set_secret();
+        let access =
+            encode_jwt(&AccessTokenClaims::new(1, "admin", "a@b.pl", 1, true, 60)).unwrap();
+        let refresh = encode_jwt(&RefreshTokenClaims::new(1, 60)).unwrap();
+
+        assert_eq!(decode_refresh_token(&refresh).unwrap(), 1);
+        assert_eq!(decode_access_token(access.clone()).unwrap().id, 1);
+        assert!(decode_refresh_token(&access).is_err());
+        assert!(decode_pickup_token(&access).is_err());
+        assert!(decode_access_token(refresh.clone()).is_err());
+        assert!(decode_pickup_token(&refresh).is_err());
+    }
+}
@@ -3,29 +3,44 @@ use actix_web::HttpRequest;
 use appstate::ActivatorsVec;
 use entity::prelude::User;
 use enums::VerificationType;
-use jwt_auth::AuthUser;
 use lettre::{
+    message::Mailbox,
     transport::smtp::{
         authentication::{Credentials, Mechanism},
         PoolConfig,
     },
-    AsyncSmtpTransport, AsyncStd1Executor, AsyncTransport,
+    AsyncSmtpTransport, AsyncStd1Executor, AsyncTransport, Message,
 };
 use log::{error, info};
 use migration::DbErr;
 use nanoid::nanoid;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
 use sea_orm::{DatabaseConnection, EntityTrait};
-use std::{fmt::Display, str::FromStr, thread};
-use stripe::{Client, Customer, CustomerId, UpdateCustomer};
+use std::fmt::Display;
 
 use errors::ServiceError;
 
 pub mod appstate;
 pub mod enums;
 pub mod errors;
+pub mod events;
+pub mod forecast;
+pub mod idempotency;
+pub mod jobs;
 pub mod jwt_auth;
+pub mod no_shows;
+pub mod order_status;
+pub mod pagination;
+pub mod payments;
+pub mod pickup;
 pub mod routes;
 pub mod scraper;
+pub mod slots;
+pub mod stock;
+pub mod subscriptions;
+pub mod validation;
+pub mod wallet;
+pub mod webhooks;
 
 const CODE_INTS: [char; 10] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
 
@@ -56,9 +71,6 @@ pub async fn send_verification_mail(
     activators: &ActivatorsVec,
     email_type: VerificationType,
 ) -> Result<String, ServiceError> {
-    let smtp_name = dotenvy::var("EMAIL_NAME").expect("NO EMAIL_NAME in .env");
-    let smtp_relay = dotenvy::var("SMTP_RELAY").expect("NO SMTP_RELAY in .env");
-    let from = format!("Kantyna-App <{}>", smtp_name).parse().unwrap();
     let to = email
         .parse()
         .map_err(|err| convert_err_to_500(err, Some("Mail creation err")))?;
@@ -67,11 +79,40 @@ pub async fn send_verification_mail(
     let code_len = email_type.code_len();
     let activation_code = nanoid!(code_len, &CODE_INTS);
     let mail = email_type
-        .email_msg(to, from, &activation_code)
+        .email_msg(to, mail_sender(), &activation_code)
         .map_err(|err| convert_err_to_500(err, Some("Mail creation err")))?;
     let mut activators = activators.write().await;
     (*activators).insert(activation_code, email.into());
 
+    spawn_mail(mail);
+
+    Ok("email send".to_string())
+}
+
+//plain text notification, sent in the background like verification mails
+pub fn send_mail(email: &str, subject: &str, text: String) -> Result<(), ServiceError> {
+    let to: Mailbox = email
+        .parse()
+        .map_err(|err| convert_err_to_500(err, Some("Mail creation err")))?;
+    let mail = Message::builder()
+        .from(mail_sender())
+        .to(to)
+        .subject(subject)
+        .body(text)
+        .map_err(|err| convert_err_to_500(err, Some("Mail creation err")))?;
+
+    spawn_mail(mail);
+    Ok(())
+}
+
+fn mail_sender() -> Mailbox {
+    let smtp_name = dotenvy::var("EMAIL_NAME").expect("NO EMAIL_NAME in .env");
+    format!("Kantyna-App <{}>", smtp_name).parse().unwrap()
+}
+
+fn spawn_mail(mail: Message) {
+    let smtp_name = dotenvy::var("EMAIL_NAME").expect("NO EMAIL_NAME in .env");
+    let smtp_relay = dotenvy::var("SMTP_RELAY").expect("NO SMTP_RELAY in .env");
     let smtp: AsyncSmtpTransport<AsyncStd1Executor> =
         AsyncSmtpTransport::<AsyncStd1Executor>::starttls_relay(&smtp_relay)
             .unwrap()
@@ -92,64 +133,40 @@ pub async fn send_verification_mail(
             }
         }
     });
-
-    Ok("email send".to_string())
 }
 
 pub fn get_header_val<'r>(req: &'r HttpRequest, key: &'r str) -> Option<&'r str> {
     req.headers().get(key)?.to_str().ok()
 }
 
-pub async fn pay(
-    client: &stripe::Client,
-    conn: &DatabaseConnection,
-    user: AuthUser,
-    amount: i64,
-) -> Result<String, ServiceError> {
-    let decr = amount;
-
-    let (customer_id, old_balance) = {
-        let customer = get_user(conn, user.id, client).await?;
-
-        (customer.id, customer.balance.unwrap())
-    };
+//quotes a value for csv exports when it needs it
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
 
-    Customer::update(
-        client,
-        &customer_id,
-        UpdateCustomer {
-            balance: Some(old_balance - decr),
-            ..Default::default()
-        },
-    )
-    .await
-    .map_err(|e| convert_err_to_500(e, Some("Stripe error")))?;
-
-    Ok("Success".into())
+//wallet balances are kept in grosze, prices in the db are decimals with 2 decimal places
+pub fn to_grosze(price: Decimal) -> i64 {
+    (price * Decimal::ONE_HUNDRED)
+        .round()
+        .to_i64()
+        .expect("price out of i64 range")
 }
 
-pub async fn get_user(
-    conn: &DatabaseConnection,
-    user_id: i32,
-    client: &Client,
-) -> Result<Customer, ServiceError> {
+//id of the user's customer at the payment provider
+pub async fn customer_id(conn: &DatabaseConnection, user_id: i32) -> Result<String, ServiceError> {
     let user = User::find_by_id(user_id)
         .one(conn)
         .await
         .map_err(map_db_err)?;
 
     let Some(user) = user else {return Err(ServiceError::BadRequest("No user has given id".into()))};
-    if let Some(user_id) = user.stripe_id {
-        let id: CustomerId = CustomerId::from_str(&user_id).unwrap();
-        let customer = Customer::retrieve(client, &id, &[])
-            .await
-            .map_err(|e| convert_err_to_500(e, Some("Stripe err")))?;
-        Ok(customer)
-    } else {
-        Err(ServiceError::BadRequest(
-            "Stripe wasn't initialized for provided user".into(),
-        ))
-    }
+    user.stripe_id.ok_or_else(|| {
+        ServiceError::BadRequest("Stripe wasn't initialized for provided user".into())
+    })
 }
 
 pub async fn init_db(conn: &DatabaseConnection) -> Result<(), ServiceError> {
@@ -1,9 +1,13 @@
 use actix_files::{Files, NamedFile};
 use actix_web::dev::{ServiceRequest, ServiceResponse};
-use actix_web::web::service;
 use async_std::sync::RwLock;
+use kantyna_api::events::EventBus;
 use kantyna_api::init_db;
-use kantyna_api::routes::{admin::*, menu::*, order::*, payment::*, users::*};
+use kantyna_api::jobs::spawn_jobs;
+use kantyna_api::payments;
+use kantyna_api::routes::{admin::*, favourites::*, feed::*, menu::*, order::*, payment::*, subscriptions::*, users::*};
+use kantyna_api::wallet::import_provider_balances;
+use kantyna_api::webhooks::replay_failed;
 use log::{error, info};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -13,7 +17,7 @@ use actix_web::middleware::Logger;
 use actix_web::{web, App, HttpServer};
 use migration::{Migrator, MigratorTrait};
 
-use kantyna_api::appstate::{AppState, ClientWrapper};
+use kantyna_api::appstate::AppState;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -26,9 +30,8 @@ async fn main() -> std::io::Result<()> {
 
     dotenvy::dotenv().expect(".env file not found");
     let db_url = dotenvy::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-    let stripe_secret =
-        dotenvy::var("STRIPE_SECRET").expect("STRIPE_SECRET is not set in .env file");
-    let stripe_client = ClientWrapper::new(&stripe_secret);
+    let payments = payments::from_env();
+    kantyna_api::routes::order::cutoff_time().expect("ORDER_CUTOFF must be HH:MM");
 
     //establish db connection
     let connection = sea_orm::Database::connect(&db_url).await.unwrap();
@@ -41,11 +44,35 @@ async fn main() -> std::io::Result<()> {
                 Migrator::fresh(&connection).await.unwrap();
                 init_db(&connection).await.map_err(|e| {
                     error!("Error during db init: {}", e);
-                    std::io::Error::new(std::io::ErrorKind::Other, "DB init err")
+                    std::io::Error::other("DB init err")
                 })?;
                 info!("DB init successful");
                 return Ok(());
             }
+            //moves balances kept on Stripe customers into the wallet ledger
+            "import-balances" => {
+                Migrator::up(&connection, None).await.unwrap();
+                let imported = import_provider_balances(&connection, payments.as_ref())
+                    .await
+                    .map_err(|e| {
+                        error!("Error during balance import: {}", e);
+                        std::io::Error::other("Balance import err")
+                    })?;
+                info!("Imported {} wallets", imported);
+                return Ok(());
+            }
+            //handles stored webhook events that failed again
+            "replay-webhooks" => {
+                Migrator::up(&connection, None).await.unwrap();
+                let replayed = replay_failed(&connection, payments.as_ref())
+                    .await
+                    .map_err(|e| {
+                        error!("Error during webhook replay: {}", e);
+                        std::io::Error::other("Webhook replay err")
+                    })?;
+                info!("Replayed {} webhook events", replayed);
+                return Ok(());
+            }
             _ => panic!("arg not supported"),
         }
     }
@@ -57,8 +84,10 @@ async fn main() -> std::io::Result<()> {
         conn: connection,
         activators_reg: Arc::new(RwLock::new(HashMap::new())),
         activators_del: Arc::new(RwLock::new(HashMap::new())),
-        stripe_client,
+        payments,
+        events: EventBus::new(256),
     });
+    spawn_jobs(state.clone());
 
     HttpServer::new(move || {
         let logger = Logger::default();
@@ -93,25 +122,77 @@ async fn main() -> std::io::Result<()> {
                     .service(
                         web::scope("/orders")
                             .service(create_order)
+                            .service(create_group_order)
+                            .service(reorder)
+                            .service(cancel_order)
+                            .service(modify_order)
+                            .service(user_order_feed)
                             .service(get_completed_user_orders)
                             .service(get_pending_user_orders)
                             .service(get_all_user_orders),
+                    )
+                    .service(
+                        web::scope("/favourites")
+                            .service(save_favourite)
+                            .service(get_favourites)
+                            .service(delete_favourite)
+                            .service(order_favourite),
+                    )
+                    .service(
+                        web::scope("/subscriptions")
+                            .service(get_subscriptions)
+                            .service(create_subscription)
+                            .service(cancel_subscription),
                     ),
             )
             .service(
-                web::scope("/admin").service(update_dish).service(
-                    web::scope("/orders")
-                        .service(get_all_pending_orders)
-                        .service(get_all_orders)
-                        .service(change_order_status),
-                ),
+                web::scope("/admin")
+                    .service(update_dish)
+                    .service(
+                        web::scope("/orders")
+                            .service(get_all_pending_orders)
+                            .service(get_all_orders)
+                            .service(change_order_status)
+                            .service(bulk_change_order_status)
+                            .service(admin_cancel_order)
+                            .service(redeem_order)
+                            .service(admin_order_feed),
+                    )
+                    .service(
+                        web::scope("/slots")
+                            .service(get_slots_admin)
+                            .service(create_slot)
+                            .service(update_slot)
+                            .service(deactivate_slot),
+                    )
+                    .service(
+                        web::scope("/no-shows")
+                            .service(get_no_show_policy)
+                            .service(update_no_show_policy)
+                            .service(get_no_show_users)
+                            .service(reset_no_shows),
+                    )
+                    .service(web::scope("/reports").service(get_forecast))
+                    .service(
+                        web::scope("/wallets")
+                            .service(adjust_balance)
+                            .service(admin_refund_balance),
+                    )
+                    .service(
+                        web::scope("/webhooks")
+                            .service(get_webhook_events)
+                            .service(replay_webhook_event),
+                    ),
             )
             .service(
                 web::scope("/payment")
                     .service(add_balance)
                     .service(init_wallet)
                     .service(get_balance)
+                    .service(get_transactions)
+                    .service(export_transactions)
                     .service(customer_details)
+                    .service(refund_balance)
                     .service(delete_wallet)
                     // .service(test_balance)
                     .service(received_payment),
@@ -121,6 +202,7 @@ async fn main() -> std::io::Result<()> {
                     .service(get_menu_all)
                     .service(get_menu_today)
                     .service(get_menu_day)
+                    .service(get_slots)
                     .service(update)
                     .service(last_menu_update),
             );
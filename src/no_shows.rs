@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Local, Utc};
+use entity::{
+    dinner_orders, model_enums::Status, no_show_policy, pickup_slot, user, user_dinner_orders,
+};
+use log::{error, info};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+
+use crate::{
+    appstate::AppState,
+    convert_err_to_500,
+    errors::ServiceError,
+    events::OrderEvent,
+    map_db_err,
+    order_status::{advance_to, change_status},
+    routes::order::PENDING_STATUSES,
+    send_mail,
+    slots::slot_end,
+    stock::collection_day,
+};
+
+pub async fn current_policy<C>(conn: &C) -> Result<no_show_policy::Model, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    no_show_policy::Entity::find()
+        .order_by_asc(no_show_policy::Column::Id)
+        .one(conn)
+        .await
+        .map_err(map_db_err)?
+        .ok_or_else(|| convert_err_to_500("no row in no_show_policy", Some("No-show policy err")))
+}
+
+//users blocked for not collecting their orders can't place new ones until the block runs out
+pub async fn ensure_can_order<C>(conn: &C, user_id: i32) -> Result<(), ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let user = user::Entity::find_by_id(user_id)
+        .one(conn)
+        .await
+        .map_err(map_db_err)?
+        .ok_or_else(|| ServiceError::BadRequest("Account does not exist".into()))?;
+
+    match user.blocked_until {
+        Some(until) if until > Utc::now() => Err(ServiceError::BadRequest(format!(
+            "Ordering is blocked until {} because of uncollected orders",
+            until.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ))),
+        _ => Ok(()),
+    }
+}
+
+//Moves every order that wasn't collected in time to NoShow and applies the policy to its owner.
+//Orders are handled one by one so a failure only skips that order until the next run
+pub async fn expire_orders(data: &AppState) -> Result<(), ServiceError> {
+    let conn = &data.conn;
+    let policy = current_policy(conn).await?;
+    let grace = Duration::minutes(policy.expire_after_minutes as i64);
+    let now = Utc::now();
+
+    //collection_date is the slot's start so this only narrows the candidates down
+    let orders = dinner_orders::Entity::find()
+        .filter(dinner_orders::Column::Status.is_in(PENDING_STATUSES))
+        .filter(dinner_orders::Column::CollectionDate.lt(now - grace))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+    if orders.is_empty() {
+        return Ok(());
+    }
+
+    let slots: HashMap<_, _> = pickup_slot::Entity::find()
+        .filter(pickup_slot::Column::Id.is_in(orders.iter().filter_map(|o| o.slot_id)))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|slot| (slot.id, slot))
+        .collect();
+
+    for order in orders {
+        let slot = order.slot_id.and_then(|id| slots.get(&id));
+        if expires_at(&order, slot, grace) > now {
+            continue;
+        }
+        if let Err(err) = expire_order(data, order.id, &policy).await {
+            error!("Expiring order {} failed: {}", order.id, err);
+        }
+    }
+
+    Ok(())
+}
+
+fn expires_at(
+    order: &dinner_orders::Model,
+    slot: Option<&pickup_slot::Model>,
+    grace: Duration,
+) -> DateTime<Utc> {
+    let end = match slot {
+        Some(slot) => slot_end(collection_day(&order.collection_date), slot),
+        None => order.collection_date,
+    };
+    end + grace
+}
+
+async fn expire_order(
+    data: &AppState,
+    order_id: i32,
+    policy: &no_show_policy::Model,
+) -> Result<(), ServiceError> {
+    let txn = data.conn.begin().await.map_err(map_db_err)?;
+    let order = dinner_orders::Entity::find_by_id(order_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(map_db_err)?;
+    //collected or cancelled since the candidates were loaded
+    let Some(order) = order
+        .filter(|o| Status::from_repr(o.status).is_some_and(|s| PENDING_STATUSES.contains(&s)))
+    else {
+        return Ok(());
+    };
+    let from = Status::from_repr(order.status).ok_or(ServiceError::InternalError)?;
+
+    //group order where at least one person showed up isn't held against the organiser
+    let collected_lines = user_dinner_orders::Entity::find()
+        .filter(user_dinner_orders::Column::OrderId.eq(order.id))
+        .filter(user_dinner_orders::Column::CollectedAt.is_not_null())
+        .count(&txn)
+        .await
+        .map_err(map_db_err)?;
+    if collected_lines > 0 {
+        let order = advance_to(
+            &txn,
+            order,
+            Status::Collected,
+            None,
+            Some("Partly collected by the group".into()),
+        )
+        .await?;
+        txn.commit().await.map_err(map_db_err)?;
+        data.events.publish(OrderEvent::StatusChanged {
+            order_id: order.id,
+            user_id: order.user_id,
+            from,
+            to: Status::Collected,
+        });
+        return Ok(());
+    }
+
+    let order = change_status(
+        &txn,
+        order,
+        Status::NoShow,
+        None,
+        Some("Not collected in time".into()),
+    )
+    .await?;
+
+    let owner = user::Entity::find_by_id(order.user_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(map_db_err)?
+        .ok_or(ServiceError::InternalError)?;
+    let no_shows = owner.no_shows + 1;
+    let email = owner.email.clone();
+    let consequence = consequence(policy, no_shows);
+
+    let mut owner: user::ActiveModel = owner.into();
+    owner.no_shows = Set(no_shows);
+    if let Consequence::Block(until) = consequence {
+        owner.blocked_until = Set(Some(until));
+    }
+    owner.update(&txn).await.map_err(map_db_err)?;
+    txn.commit().await.map_err(map_db_err)?;
+
+    info!(
+        "Order {} of user {} expired as no-show ({} so far)",
+        order.id, order.user_id, no_shows
+    );
+    data.events.publish(OrderEvent::StatusChanged {
+        order_id: order.id,
+        user_id: order.user_id,
+        from,
+        to: Status::NoShow,
+    });
+
+    notify(&email, order.id, consequence)
+}
+
+enum Consequence {
+    Nothing,
+    Warning { left: i32 },
+    Block(DateTime<Utc>),
+}
+
+//every block_after-th no-show blocks ordering, warnings are sent from warn_after no-shows
+//since the last block onwards, 0 in the policy turns given step off
+fn consequence(policy: &no_show_policy::Model, no_shows: i32) -> Consequence {
+    if policy.block_after > 0 && no_shows % policy.block_after == 0 {
+        return Consequence::Block(Utc::now() + Duration::days(policy.block_days as i64));
+    }
+
+    let strikes = match policy.block_after {
+        0 => no_shows,
+        block_after => no_shows % block_after,
+    };
+    if policy.warn_after > 0 && strikes >= policy.warn_after {
+        let left = match policy.block_after {
+            0 => 0,
+            block_after => block_after - strikes,
+        };
+        return Consequence::Warning { left };
+    }
+
+    Consequence::Nothing
+}
+
+fn notify(email: &str, order_id: i32, consequence: Consequence) -> Result<(), ServiceError> {
+    let text = match consequence {
+        Consequence::Nothing => return Ok(()),
+        Consequence::Warning { left: 0 } => format!(
+            "Zamówienie nr {} nie zostało odebrane.\nProsimy o odbieranie zamówionych posiłków.",
+            order_id
+        ),
+        Consequence::Warning { left } => format!(
+            "Zamówienie nr {} nie zostało odebrane.\nPo {} kolejnych nieodebranych zamówieniach możliwość zamawiania zostanie czasowo zablokowana.",
+            order_id, left
+        ),
+        Consequence::Block(until) => format!(
+            "Zamówienie nr {} nie zostało odebrane.\nZ powodu nieodebranych zamówień możliwość zamawiania jest zablokowana do {}.",
+            order_id,
+            until.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ),
+    };
+
+    send_mail(email, "Kantyna - nieodebrane zamówienie", text)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::slots::slot_start;
+
+    //blocks for a week, the thresholds are set by each test
+    fn policy(warn_after: i32, block_after: i32) -> no_show_policy::Model {
+        no_show_policy::Model {
+            id: 1,
+            expire_after_minutes: 30,
+            warn_after,
+            block_after,
+            block_days: 7,
+        }
+    }
+
+    fn left_after_warning(policy: &no_show_policy::Model, no_shows: i32) -> Option<i32> {
+        match consequence(policy, no_shows) {
+            Consequence::Warning { left } => Some(left),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn warnings_start_at_the_threshold() {
+        let policy = policy(2, 4);
+
+        assert!(matches!(consequence(&policy, 1), Consequence::Nothing));
+        assert_eq!(left_after_warning(&policy, 2), Some(2));
+        assert_eq!(left_after_warning(&policy, 3), Some(1));
+    }
+
+    #[test]
+    fn every_block_after_th_no_show_blocks() {
+        let policy = policy(2, 4);
+
+        for no_shows in [4, 8] {
+            let Consequence::Block(until) = consequence(&policy, no_shows) else {
+                panic!("{} no-shows didn't block", no_shows);
+            };
+            let minutes = (until - Utc::now()).num_minutes();
+            assert!((7 * 24 * 60 - 1..=7 * 24 * 60).contains(&minutes));
+        }
+        //counting starts over after a block
+        assert!(matches!(consequence(&policy, 5), Consequence::Nothing));
+        assert_eq!(left_after_warning(&policy, 6), Some(2));
+    }
+
+    #[test]
+    fn zero_turns_a_step_off() {
+        assert!(matches!(
+            consequence(&policy(0, 4), 3),
+            Consequence::Nothing
+        ));
+        assert!(matches!(
+            consequence(&policy(0, 0), 10),
+            Consequence::Nothing
+        ));
+        assert_eq!(left_after_warning(&policy(2, 0), 10), Some(0));
+    }
+
+    fn order(collection_date: DateTime<Utc>) -> dinner_orders::Model {
+        dinner_orders::Model {
+            id: 1,
+            user_id: 1,
+            collection_date,
+            status: 0,
+            price: Decimal::new(1500, 2),
+            cancelled_by: None,
+            cancelled_at: None,
+            cancel_reason: None,
+            pickup_code: None,
+            slot_id: Some(3),
+            note: None,
+            is_group: 0,
+        }
+    }
+
+    #[test]
+    fn order_expires_after_its_slot_ends() {
+        let day = NaiveDate::from_ymd_opt(2023, 5, 22).unwrap();
+        let slot = pickup_slot::Model {
+            id: 3,
+            week_day: 0,
+            start_time: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            capacity: 20,
+            active: 1,
+        };
+        let order = order(slot_start(day, &slot));
+        let grace = Duration::minutes(30);
+
+        let expires = expires_at(&order, Some(&slot), grace);
+
+        assert_eq!(expires, slot_end(day, &slot) + grace);
+        assert_eq!(expires - order.collection_date, Duration::minutes(90));
+    }
+
+    #[test]
+    fn order_without_slot_expires_after_its_collection_date() {
+        let order = order(Utc::now());
+
+        let expires = expires_at(&order, None, Duration::minutes(30));
+
+        assert_eq!(expires, order.collection_date + Duration::minutes(30));
+    }
+}
@@ -0,0 +1,204 @@
+use chrono::Utc;
+use entity::{dinner_orders, model_enums::Status, order_history};
+use sea_orm::{ActiveEnum, ActiveModelTrait, ConnectionTrait, EntityTrait, Set};
+
+use crate::{errors::ServiceError, map_db_err};
+
+pub async fn record_status<C>(
+    conn: &C,
+    order_id: i32,
+    from: Option<Status>,
+    to: Status,
+    changed_by: Option<i32>,
+    note: Option<String>,
+) -> Result<(), ServiceError>
+where
+    C: ConnectionTrait,
+{
+    if note.as_ref().is_some_and(|n| n.chars().count() > 255) {
+        return Err(ServiceError::BadRequest(
+            "Status note can't be longer than 255 characters".into(),
+        ));
+    }
+
+    order_history::Entity::insert(order_history::ActiveModel {
+        order_id: Set(order_id),
+        from_status: Set(from.map(|status| status.into_value())),
+        to_status: Set(to.into_value()),
+        changed_by: Set(changed_by),
+        changed_at: Set(Utc::now()),
+        note: Set(note),
+        ..Default::default()
+    })
+    .exec(conn)
+    .await
+    .map_err(map_db_err)?;
+
+    Ok(())
+}
+
+//Moves order to new_status if Status::next allows it and records the change in order_history.
+//Caller should hold a lock on the order row so two changes can't race each other
+pub async fn change_status<C>(
+    conn: &C,
+    order: dinner_orders::Model,
+    new_status: Status,
+    changed_by: Option<i32>,
+    note: Option<String>,
+) -> Result<dinner_orders::Model, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let status = Status::from_repr(order.status).ok_or(ServiceError::InternalError)?;
+    if !status.can_transition_to(new_status) {
+        return Err(ServiceError::Conflict(format!(
+            "Order {} can't be moved from {:?} to {:?}",
+            order.id, status, new_status
+        )));
+    }
+
+    let order_id = order.id;
+    let mut order: dinner_orders::ActiveModel = order.into();
+    order.status = Set(new_status.into_value());
+    let order = order.update(conn).await.map_err(map_db_err)?;
+
+    record_status(conn, order_id, Some(status), new_status, changed_by, note).await?;
+
+    Ok(order)
+}
+
+//Walks order along Status::forward until it reaches `target`, every step lands in history
+pub async fn advance_to<C>(
+    conn: &C,
+    mut order: dinner_orders::Model,
+    target: Status,
+    changed_by: Option<i32>,
+    note: Option<String>,
+) -> Result<dinner_orders::Model, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    loop {
+        let status = Status::from_repr(order.status).ok_or(ServiceError::InternalError)?;
+        if status == target {
+            return Ok(order);
+        }
+
+        let Some(next) = status.forward() else {
+            return Err(ServiceError::Conflict(format!(
+                "Order {} can't be moved from {:?} to {:?}",
+                order.id, status, target
+            )));
+        };
+        order = change_status(conn, order, next, changed_by, note.clone()).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use sea_orm::{DbBackend, Iterable, MockDatabase, MockExecResult};
+
+    use super::*;
+
+    //every move the lifecycle allows, anything else has to be refused
+    const ALLOWED: [(Status, Status); 7] = [
+        (Status::Paid, Status::Prepared),
+        (Status::Paid, Status::Cancelled),
+        (Status::Paid, Status::NoShow),
+        (Status::Prepared, Status::Ready),
+        (Status::Prepared, Status::NoShow),
+        (Status::Ready, Status::Collected),
+        (Status::Ready, Status::NoShow),
+    ];
+
+    fn order(status: Status) -> dinner_orders::Model {
+        dinner_orders::Model {
+            id: 1,
+            user_id: 1,
+            collection_date: Utc::now(),
+            status: status.into_value(),
+            price: Decimal::new(1500, 2),
+            cancelled_by: None,
+            cancelled_at: None,
+            cancel_reason: None,
+            pickup_code: None,
+            slot_id: None,
+            note: None,
+            is_group: 0,
+        }
+    }
+
+    #[test]
+    fn only_lifecycle_moves_are_allowed() {
+        for from in Status::iter() {
+            for to in Status::iter() {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    ALLOWED.contains(&(from, to)),
+                    "{:?} -> {:?}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn finished_orders_stay_finished() {
+        assert!(!Status::Collected.can_transition_to(Status::Paid));
+        assert!(!Status::NoShow.can_transition_to(Status::Paid));
+        assert!(Status::iter().all(|to| !Status::Cancelled.can_transition_to(to)));
+    }
+
+    #[actix_rt::test]
+    async fn allowed_change_is_recorded() {
+        for (from, to) in ALLOWED {
+            let conn = MockDatabase::new(DbBackend::MySql)
+                .append_query_results([[order(to)]])
+                .append_exec_results([
+                    MockExecResult {
+                        last_insert_id: 0,
+                        rows_affected: 1,
+                    },
+                    MockExecResult {
+                        last_insert_id: 1,
+                        rows_affected: 1,
+                    },
+                ])
+                .into_connection();
+
+            let changed = change_status(&conn, order(from), to, Some(2), None)
+                .await
+                .unwrap();
+
+            assert_eq!(Status::from_repr(changed.status), Some(to));
+            let log = format!("{:?}", conn.into_transaction_log());
+            assert!(log.contains("INSERT INTO `order_history`"), "{}", log);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn refused_change_touches_nothing() {
+        let refused = [
+            (Status::Collected, Status::Paid),
+            (Status::Cancelled, Status::Paid),
+            (Status::Cancelled, Status::Prepared),
+            (Status::NoShow, Status::Paid),
+            (Status::Paid, Status::Collected),
+        ];
+        for (from, to) in refused {
+            let conn = MockDatabase::new(DbBackend::MySql).into_connection();
+
+            let result = change_status(&conn, order(from), to, Some(2), None).await;
+
+            assert!(
+                matches!(result, Err(ServiceError::Conflict(_))),
+                "{:?} -> {:?}",
+                from,
+                to
+            );
+            assert!(conn.into_transaction_log().is_empty());
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use actix_web::{web, FromRequest};
+use sea_orm::{ConnectionTrait, PaginatorTrait, SelectorTrait};
+use serde::{de::DeserializeOwned, de::IntoDeserializer, Deserialize, Deserializer, Serialize};
+
+use crate::{errors::ServiceError, map_db_err};
+
+const DEFAULT_PER_PAGE: u64 = 20;
+const MAX_PER_PAGE: u64 = 100;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageQuery {
+    page: Option<u64>,
+    per_page: Option<u64>,
+}
+
+//`?page=2&perPage=50` on any list endpoint, pages are counted from 1
+#[derive(Clone, Copy)]
+pub struct Pagination {
+    pub page: u64,
+    pub per_page: u64,
+}
+
+impl FromRequest for Pagination {
+    type Error = ServiceError;
+    type Future = std::future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let query = match web::Query::<PageQuery>::from_query(req.query_string()) {
+            Ok(query) => query.into_inner(),
+            Err(err) => return std::future::ready(Err(ServiceError::BadRequest(err.to_string()))),
+        };
+
+        let page = query.page.unwrap_or(1);
+        let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE);
+        if page == 0 || per_page == 0 || per_page > MAX_PER_PAGE {
+            return std::future::ready(Err(ServiceError::BadRequest(format!(
+                "page has to be at least 1 and perPage between 1 and {}",
+                MAX_PER_PAGE
+            ))));
+        }
+
+        std::future::ready(Ok(Pagination { page, per_page }))
+    }
+}
+
+impl Pagination {
+    //Fetches requested page of `select` together with the number of all matching rows
+    pub async fn fetch<'db, C, S>(
+        &self,
+        conn: &'db C,
+        select: S,
+    ) -> Result<(Vec<<S::Selector as SelectorTrait>::Item>, u64), ServiceError>
+    where
+        C: ConnectionTrait,
+        S: PaginatorTrait<'db, C>,
+    {
+        let paginator = select.paginate(conn, self.per_page);
+        let total = paginator.num_items().await.map_err(map_db_err)?;
+        let items = paginator
+            .fetch_page(self.page - 1)
+            .await
+            .map_err(map_db_err)?;
+
+        Ok((items, total))
+    }
+
+    pub fn page_of<T>(&self, data: T, total: u64) -> Page<T> {
+        Page {
+            data,
+            total,
+            page: self.page,
+            per_page: self.per_page,
+            total_pages: total.div_ceil(self.per_page),
+        }
+    }
+}
+
+//response envelope, fields of `data` are inlined next to the counters
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    #[serde(flatten)]
+    pub data: T,
+    pub total: u64,
+    pub page: u64,
+    pub per_page: u64,
+    pub total_pages: u64,
+}
+
+//`Paid,Ready` style query params
+pub fn comma_separated<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let Some(list) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    list.split(',')
+        .map(|item| T::deserialize(item.trim().to_string().into_deserializer()))
+        .collect::<Result<Vec<_>, serde::de::value::Error>>()
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
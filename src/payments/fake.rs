@@ -0,0 +1,277 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+use serde_json::Value;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::{CustomerDetails, NewCustomer, PaymentProvider, WebhookEvent, WebhookEventKind};
+use crate::errors::ServiceError;
+
+//Deterministic stand-in for Stripe kept in memory, ids are numbered in order of creation.
+//Payments succeed only when a webhook built by `intent_succeeded` is delivered, the other
+//event builders stand in for things that happen on Stripe's side.
+//Webhooks are signed with a secret like Stripe's, so nobody without it can forge a top-up
+#[derive(Debug)]
+pub struct FakeProvider {
+    webhook_secret: String,
+    state: Mutex<FakeState>,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    last_id: u64,
+    customers: HashMap<String, CustomerDetails>,
+    intents: HashMap<String, FakeIntent>,
+}
+
+#[derive(Debug)]
+struct FakeIntent {
+    customer_id: String,
+    amount: i64,
+    refunded: i64,
+    //id of every refund keyed by its reference
+    refunds: HashMap<String, String>,
+}
+
+impl FakeState {
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.last_id += 1;
+        format!("{}_fake_{}", prefix, self.last_id)
+    }
+}
+
+impl FakeProvider {
+    pub fn new(webhook_secret: impl Into<String>) -> Self {
+        FakeProvider {
+            webhook_secret: webhook_secret.into(),
+            state: Default::default(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, FakeState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.webhook_secret.as_bytes())
+            .expect("hmac takes keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    //signature the fake accepts for given webhook payload, hex encoded HMAC-SHA256
+    pub fn sign(&self, payload: &str) -> String {
+        hex::encode(self.mac(payload).finalize().into_bytes())
+    }
+
+    //payload and signature of the webhook Stripe would send once the intent is paid
+    pub fn intent_succeeded(&self, intent_id: &str) -> (String, String) {
+        self.event(
+            "payment_intent.succeeded",
+            serde_json::json!({ "id": intent_id }),
+        )
+    }
+
+    pub fn intent_failed(&self, intent_id: &str, reason: &str) -> (String, String) {
+        self.event(
+            "payment_intent.payment_failed",
+            serde_json::json!({ "id": intent_id, "last_payment_error": { "message": reason } }),
+        )
+    }
+
+    //sent after every refund of the intent, with the total refunded so far.
+    //Every intent has a single charge here, named after it
+    pub fn charge_refunded(&self, intent_id: &str) -> (String, String) {
+        let refunded = self.state().intents.get(intent_id).map(|i| i.refunded);
+        self.event(
+            "charge.refunded",
+            serde_json::json!({
+                "id": intent_id.replacen("pi_", "ch_", 1),
+                "payment_intent": intent_id,
+                "amount_refunded": refunded.unwrap_or_default(),
+            }),
+        )
+    }
+
+    pub fn dispute_created(&self, intent_id: &str, amount: i64) -> (String, String) {
+        let dispute_id = self.state().next_id("dp");
+        self.event(
+            "charge.dispute.created",
+            serde_json::json!({
+                "id": dispute_id,
+                "payment_intent": intent_id,
+                "amount": amount,
+                "reason": "fraudulent",
+            }),
+        )
+    }
+
+    fn event(&self, event_type: &str, object: Value) -> (String, String) {
+        let event_id = self.state().next_id("evt");
+        let payload = serde_json::json!({
+            "id": event_id,
+            "type": event_type,
+            "data": { "object": object },
+        })
+        .to_string();
+        let signature = self.sign(&payload);
+        (payload, signature)
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for FakeProvider {
+    async fn create_customer(&self, customer: NewCustomer<'_>) -> Result<String, ServiceError> {
+        let mut state = self.state();
+        let id = state.next_id("cus");
+        let details = CustomerDetails {
+            id: id.clone(),
+            email: Some(customer.email.to_string()),
+            name: Some(customer.name.to_string()),
+            phone: Some(customer.phone.to_string()),
+            address: Some(customer.address.into()),
+            balance: 0,
+        };
+        state.customers.insert(id.clone(), details);
+        Ok(id)
+    }
+
+    async fn customer(&self, customer_id: &str) -> Result<CustomerDetails, ServiceError> {
+        self.state()
+            .customers
+            .get(customer_id)
+            .cloned()
+            .ok_or_else(|| ServiceError::BadRequest("No customer has given id".into()))
+    }
+
+    async fn delete_customer(&self, customer_id: &str) -> Result<(), ServiceError> {
+        self.state()
+            .customers
+            .remove(customer_id)
+            .map(|_| ())
+            .ok_or_else(|| ServiceError::BadRequest("No customer has given id".into()))
+    }
+
+    async fn create_intent(&self, customer_id: &str, amount: i64) -> Result<String, ServiceError> {
+        let mut state = self.state();
+        if !state.customers.contains_key(customer_id) {
+            return Err(ServiceError::BadRequest("No customer has given id".into()));
+        }
+        let id = state.next_id("pi");
+        state.intents.insert(
+            id.clone(),
+            FakeIntent {
+                customer_id: customer_id.to_string(),
+                amount,
+                refunded: 0,
+                refunds: HashMap::new(),
+            },
+        );
+        Ok(format!("{}_secret", id))
+    }
+
+    fn verify_webhook(&self, payload: &str, signature: &str) -> Result<WebhookEvent, ServiceError> {
+        let valid = hex::decode(signature)
+            .is_ok_and(|signature| self.mac(payload).verify_slice(&signature).is_ok());
+        if !valid {
+            return Err(ServiceError::BadRequest("Invalid webhook signature".into()));
+        }
+        self.parse_webhook(payload)
+    }
+
+    fn parse_webhook(&self, payload: &str) -> Result<WebhookEvent, ServiceError> {
+        let event: Value = serde_json::from_str(payload)
+            .map_err(|_| ServiceError::BadRequest("Invalid webhook payload".into()))?;
+        let id = event["id"].as_str().unwrap_or_default().to_string();
+        let event_type = event["type"].as_str().unwrap_or_default().to_string();
+        let object = &event["data"]["object"];
+        let object_id = object["id"].as_str().unwrap_or_default();
+        let intent_id = object["payment_intent"].as_str().map(str::to_string);
+
+        let kind = match event_type.as_str() {
+            "payment_intent.succeeded" => {
+                let state = self.state();
+                let intent = state
+                    .intents
+                    .get(object_id)
+                    .ok_or_else(|| ServiceError::BadRequest("No intent has given id".into()))?;
+                WebhookEventKind::PaymentSucceeded {
+                    intent_id: object_id.to_string(),
+                    customer_id: Some(intent.customer_id.clone()),
+                    amount: intent.amount,
+                }
+            }
+            "payment_intent.payment_failed" => WebhookEventKind::PaymentFailed {
+                intent_id: object_id.to_string(),
+                customer_id: self
+                    .state()
+                    .intents
+                    .get(object_id)
+                    .map(|i| i.customer_id.clone()),
+                reason: object["last_payment_error"]["message"]
+                    .as_str()
+                    .map(str::to_string),
+            },
+            "charge.refunded" => WebhookEventKind::ChargeRefunded {
+                charge_id: object_id.to_string(),
+                intent_id,
+                amount_refunded: object["amount_refunded"].as_i64().unwrap_or_default(),
+            },
+            "charge.dispute.created" => WebhookEventKind::DisputeCreated {
+                dispute_id: object_id.to_string(),
+                intent_id,
+                amount: object["amount"].as_i64().unwrap_or_default(),
+                reason: object["reason"].as_str().unwrap_or_default().to_string(),
+            },
+            _ => WebhookEventKind::Other,
+        };
+
+        Ok(WebhookEvent {
+            id,
+            event_type,
+            kind,
+        })
+    }
+
+    async fn refund(
+        &self,
+        intent_id: &str,
+        amount: i64,
+        reference: &str,
+    ) -> Result<String, ServiceError> {
+        let mut state = self.state();
+        let intent = state
+            .intents
+            .get(intent_id)
+            .ok_or_else(|| ServiceError::BadRequest("No intent has given id".into()))?;
+        if amount <= 0 || intent.refunded + amount > intent.amount {
+            return Err(ServiceError::BadRequest(
+                "Refund exceeds the payment".into(),
+            ));
+        }
+        let refund_id = state.next_id("re");
+        let intent = state
+            .intents
+            .get_mut(intent_id)
+            .expect("intent checked above");
+        intent.refunded += amount;
+        intent
+            .refunds
+            .insert(reference.to_string(), refund_id.clone());
+        Ok(refund_id)
+    }
+
+    async fn find_refund(
+        &self,
+        intent_id: &str,
+        reference: &str,
+    ) -> Result<Option<String>, ServiceError> {
+        let state = self.state();
+        let intent = state
+            .intents
+            .get(intent_id)
+            .ok_or_else(|| ServiceError::BadRequest("No intent has given id".into()))?;
+        Ok(intent.refunds.get(reference).cloned())
+    }
+}
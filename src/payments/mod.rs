@@ -0,0 +1,142 @@
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::{errors::ServiceError, routes::structs::Address};
+
+mod fake;
+mod stripe_provider;
+
+pub use fake::FakeProvider;
+pub use stripe_provider::StripeProvider;
+
+//Everything the app needs from whoever collects the money, wallets themselves live in the ledger.
+//Stripe in production, the in-memory fake to run the whole flow without network
+#[async_trait]
+pub trait PaymentProvider: fmt::Debug + Send + Sync {
+    //returns id of the new customer
+    async fn create_customer(&self, customer: NewCustomer<'_>) -> Result<String, ServiceError>;
+
+    async fn customer(&self, customer_id: &str) -> Result<CustomerDetails, ServiceError>;
+
+    async fn delete_customer(&self, customer_id: &str) -> Result<(), ServiceError>;
+
+    //payment of `amount` grosze finished by the client, returns the secret it needs for that
+    async fn create_intent(&self, customer_id: &str, amount: i64) -> Result<String, ServiceError>;
+
+    //checks the signature of a webhook delivery and parses it
+    fn verify_webhook(&self, payload: &str, signature: &str) -> Result<WebhookEvent, ServiceError>;
+
+    //parses a stored delivery again without the signature, it was checked when received
+    fn parse_webhook(&self, payload: &str) -> Result<WebhookEvent, ServiceError>;
+
+    //gives `amount` grosze of a succeeded payment back, returns id of the refund.
+    //`reference` is kept with the refund so it can be found again with `find_refund`
+    async fn refund(
+        &self,
+        intent_id: &str,
+        amount: i64,
+        reference: &str,
+    ) -> Result<String, ServiceError>;
+
+    //id of the refund of given payment made with `reference`, None when it was never made
+    //or the provider gave up on it
+    async fn find_refund(
+        &self,
+        intent_id: &str,
+        reference: &str,
+    ) -> Result<Option<String>, ServiceError>;
+}
+
+//picked with PAYMENT_PROVIDER (stripe by default or fake)
+pub fn from_env() -> Arc<dyn PaymentProvider> {
+    let provider = dotenvy::var("PAYMENT_PROVIDER").unwrap_or_else(|_| "stripe".into());
+    match provider.as_str() {
+        "stripe" => {
+            let secret =
+                dotenvy::var("STRIPE_SECRET").expect("STRIPE_SECRET is not set in .env file");
+            let webhook_secret =
+                dotenvy::var("WEBHOOK_SECRET").expect("No WEBHOOK_SECRET provided in .env");
+            Arc::new(StripeProvider::new(&secret, webhook_secret))
+        }
+        "fake" => {
+            let webhook_secret =
+                dotenvy::var("WEBHOOK_SECRET").expect("No WEBHOOK_SECRET provided in .env");
+            Arc::new(FakeProvider::new(webhook_secret))
+        }
+        other => panic!("PAYMENT_PROVIDER {} is not supported", other),
+    }
+}
+
+pub struct NewCustomer<'a> {
+    pub email: &'a str,
+    pub name: &'a str,
+    pub phone: &'a str,
+    pub address: &'a Address,
+}
+
+//field names follow Stripe's customer object which the endpoint used to return as is
+#[derive(Serialize, Clone, Debug)]
+pub struct CustomerDetails {
+    pub id: String,
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<CustomerAddress>,
+    //left on the customer from before the ledger, only read by the import
+    #[serde(skip)]
+    pub balance: i64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CustomerAddress {
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub postal_code: Option<String>,
+    pub state: Option<String>,
+}
+
+impl From<&Address> for CustomerAddress {
+    fn from(address: &Address) -> Self {
+        CustomerAddress {
+            city: Some(address.city.clone()),
+            country: Some(address.country.clone()),
+            postal_code: Some(address.postal_code.clone()),
+            state: Some(address.state.clone()),
+        }
+    }
+}
+
+pub struct WebhookEvent {
+    pub id: String,
+    pub event_type: String,
+    pub kind: WebhookEventKind,
+}
+
+pub enum WebhookEventKind {
+    PaymentSucceeded {
+        intent_id: String,
+        customer_id: Option<String>,
+        amount: i64,
+    },
+    PaymentFailed {
+        intent_id: String,
+        customer_id: Option<String>,
+        reason: Option<String>,
+    },
+    //refunded from the dashboard or through the api, `amount_refunded` is the total so far
+    ChargeRefunded {
+        charge_id: String,
+        intent_id: Option<String>,
+        amount_refunded: i64,
+    },
+    DisputeCreated {
+        dispute_id: String,
+        intent_id: Option<String>,
+        amount: i64,
+        reason: String,
+    },
+    //types the app doesn't act on, acknowledged anyway so they aren't retried
+    Other,
+}
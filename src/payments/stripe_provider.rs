@@ -0,0 +1,215 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use async_trait::async_trait;
+use stripe::{
+    CreateCustomer, CreatePaymentIntent, CreateRefund, Customer, CustomerId, EventObject,
+    EventType, ListRefunds, PaymentIntent, PaymentIntentId, Refund, StripeError, Webhook,
+    WebhookEvent as StripeEvent,
+};
+
+use super::{
+    CustomerAddress, CustomerDetails, NewCustomer, PaymentProvider, WebhookEvent, WebhookEventKind,
+};
+use crate::{convert_err_to_500, errors::ServiceError};
+
+//metadata key of the app's own reference of a refund
+const REFERENCE_KEY: &str = "reference";
+
+pub struct StripeProvider {
+    client: stripe::Client,
+    webhook_secret: String,
+}
+
+impl fmt::Debug for StripeProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stripe provider").finish()
+    }
+}
+
+impl StripeProvider {
+    pub fn new(secret_key: &str, webhook_secret: String) -> Self {
+        StripeProvider {
+            client: stripe::Client::new(secret_key),
+            webhook_secret,
+        }
+    }
+}
+
+fn stripe_err(err: StripeError) -> ServiceError {
+    convert_err_to_500(err, Some("Stripe error"))
+}
+
+fn customer_id(id: &str) -> Result<CustomerId, ServiceError> {
+    CustomerId::from_str(id).map_err(|e| convert_err_to_500(e, Some("Stripe customer id err")))
+}
+
+fn intent_id(id: &str) -> Result<PaymentIntentId, ServiceError> {
+    PaymentIntentId::from_str(id).map_err(|e| convert_err_to_500(e, Some("Stripe intent id err")))
+}
+
+fn webhook_event(event: StripeEvent) -> WebhookEvent {
+    let event_type = serde_json::to_value(event.event_type)
+        .ok()
+        .and_then(|t| t.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let kind = match (event.event_type, event.data.object) {
+        (EventType::PaymentIntentSucceeded, EventObject::PaymentIntent(intent)) => {
+            WebhookEventKind::PaymentSucceeded {
+                intent_id: intent.id.to_string(),
+                customer_id: intent.customer.map(|c| c.id().to_string()),
+                amount: intent.amount,
+            }
+        }
+        (EventType::PaymentIntentPaymentFailed, EventObject::PaymentIntent(intent)) => {
+            WebhookEventKind::PaymentFailed {
+                intent_id: intent.id.to_string(),
+                customer_id: intent.customer.map(|c| c.id().to_string()),
+                reason: intent.last_payment_error.and_then(|e| e.message),
+            }
+        }
+        (EventType::ChargeRefunded, EventObject::Charge(charge)) => {
+            WebhookEventKind::ChargeRefunded {
+                charge_id: charge.id.to_string(),
+                intent_id: charge.payment_intent.map(|i| i.id().to_string()),
+                amount_refunded: charge.amount_refunded,
+            }
+        }
+        (EventType::ChargeDisputeCreated, EventObject::Dispute(dispute)) => {
+            WebhookEventKind::DisputeCreated {
+                dispute_id: dispute.id.to_string(),
+                intent_id: dispute.payment_intent.map(|i| i.id().to_string()),
+                amount: dispute.amount,
+                reason: dispute.reason,
+            }
+        }
+        _ => WebhookEventKind::Other,
+    };
+
+    WebhookEvent {
+        id: event.id.to_string(),
+        event_type,
+        kind,
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for StripeProvider {
+    async fn create_customer(&self, customer: NewCustomer<'_>) -> Result<String, ServiceError> {
+        let address = &customer.address;
+        let address = stripe::Address {
+            city: Some(address.city.clone()),
+            country: Some(address.country.clone()),
+            postal_code: Some(address.postal_code.clone()),
+            state: Some(address.state.clone()),
+            ..Default::default()
+        };
+        let created = Customer::create(
+            &self.client,
+            CreateCustomer {
+                email: Some(customer.email),
+                name: Some(customer.name),
+                phone: Some(customer.phone),
+                address: Some(address),
+                metadata: Some(HashMap::from([("async-stripe".into(), "true".into())])),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(stripe_err)?;
+
+        Ok(created.id.to_string())
+    }
+
+    async fn customer(&self, customer_id: &str) -> Result<CustomerDetails, ServiceError> {
+        let id = self::customer_id(customer_id)?;
+        let customer = Customer::retrieve(&self.client, &id, &[])
+            .await
+            .map_err(stripe_err)?;
+
+        Ok(CustomerDetails {
+            id: customer.id.to_string(),
+            email: customer.email,
+            name: customer.name,
+            phone: customer.phone,
+            address: customer.address.map(|address| CustomerAddress {
+                city: address.city,
+                country: address.country,
+                postal_code: address.postal_code,
+                state: address.state,
+            }),
+            balance: customer.balance.unwrap_or(0),
+        })
+    }
+
+    async fn delete_customer(&self, customer_id: &str) -> Result<(), ServiceError> {
+        let id = self::customer_id(customer_id)?;
+        Customer::delete(&self.client, &id)
+            .await
+            .map_err(stripe_err)?;
+        Ok(())
+    }
+
+    async fn create_intent(&self, customer_id: &str, amount: i64) -> Result<String, ServiceError> {
+        let mut intent = CreatePaymentIntent::new(amount, stripe::Currency::PLN);
+        intent.payment_method_types = Some(vec!["card".into(), "p24".into()]);
+        intent.customer = Some(self::customer_id(customer_id)?);
+        intent.expand = &["customer"];
+
+        let intent = PaymentIntent::create(&self.client, intent)
+            .await
+            .map_err(|e| convert_err_to_500(e, Some("Stripe Error")))?;
+        intent
+            .client_secret
+            .ok_or_else(|| convert_err_to_500("intent without client secret", Some("Stripe Error")))
+    }
+
+    fn verify_webhook(&self, payload: &str, signature: &str) -> Result<WebhookEvent, ServiceError> {
+        let Ok(event) = Webhook::construct_event(payload, signature, &self.webhook_secret) else {
+            return Err(ServiceError::BadRequest("Invalid webhook signature".into()));
+        };
+        Ok(webhook_event(event))
+    }
+
+    fn parse_webhook(&self, payload: &str) -> Result<WebhookEvent, ServiceError> {
+        let event: StripeEvent = serde_json::from_str(payload)
+            .map_err(|e| convert_err_to_500(e, Some("Stripe webhook payload err")))?;
+        Ok(webhook_event(event))
+    }
+
+    async fn refund(
+        &self,
+        intent_id: &str,
+        amount: i64,
+        reference: &str,
+    ) -> Result<String, ServiceError> {
+        let mut refund = CreateRefund::new();
+        refund.payment_intent = Some(self::intent_id(intent_id)?);
+        refund.amount = Some(amount);
+        refund.metadata = Some(HashMap::from([(REFERENCE_KEY.into(), reference.into())]));
+
+        let refund = Refund::create(&self.client, refund)
+            .await
+            .map_err(stripe_err)?;
+        Ok(refund.id.to_string())
+    }
+
+    async fn find_refund(
+        &self,
+        intent_id: &str,
+        reference: &str,
+    ) -> Result<Option<String>, ServiceError> {
+        let mut params = ListRefunds::new();
+        params.payment_intent = Some(self::intent_id(intent_id)?);
+        params.limit = Some(100);
+
+        let refunds = Refund::list(&self.client, &params)
+            .await
+            .map_err(stripe_err)?;
+        Ok(refunds
+            .data
+            .into_iter()
+            .filter(|r| !matches!(r.status.as_deref(), Some("failed" | "canceled")))
+            .find(|r| r.metadata.get(REFERENCE_KEY).map(String::as_str) == Some(reference))
+            .map(|r| r.id.to_string()))
+    }
+}
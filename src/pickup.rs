@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use entity::{dinner_orders, user_dinner_orders};
+use nanoid::nanoid;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, EntityTrait, JoinType, PaginatorTrait, QueryFilter, QuerySelect,
+    RelationTrait,
+};
+
+use crate::{
+    convert_err_to_500,
+    errors::ServiceError,
+    jwt_auth::{encode_jwt, PickupClaims},
+    map_db_err,
+    stock::{collection_day, day_bounds},
+};
+
+//no 0/O, 1/I so codes can be read out loud or typed in by hand
+const PICKUP_ALPHABET: [char; 32] = [
+    '2', '3', '4', '5', '6', '7', '8', '9', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'J', 'K', 'L',
+    'M', 'N', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z',
+];
+const PICKUP_CODE_LEN: usize = 6;
+
+//Short code unique among orders and group order lines collected on the same day
+pub async fn generate_pickup_code<C>(
+    conn: &C,
+    collection_date: &DateTime<Utc>,
+) -> Result<String, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let (start, end) = day_bounds(collection_day(collection_date));
+
+    for _ in 0..5 {
+        let code = nanoid!(PICKUP_CODE_LEN, &PICKUP_ALPHABET);
+        let taken = dinner_orders::Entity::find()
+            .filter(dinner_orders::Column::PickupCode.eq(code.as_str()))
+            .filter(dinner_orders::Column::CollectionDate.gte(start))
+            .filter(dinner_orders::Column::CollectionDate.lt(end))
+            .count(conn)
+            .await
+            .map_err(map_db_err)?;
+        let taken_by_line = user_dinner_orders::Entity::find()
+            .join(
+                JoinType::InnerJoin,
+                user_dinner_orders::Relation::DinnerOrders.def(),
+            )
+            .filter(user_dinner_orders::Column::PickupCode.eq(code.as_str()))
+            .filter(dinner_orders::Column::CollectionDate.gte(start))
+            .filter(dinner_orders::Column::CollectionDate.lt(end))
+            .count(conn)
+            .await
+            .map_err(map_db_err)?;
+
+        if taken + taken_by_line == 0 {
+            return Ok(code);
+        }
+    }
+
+    Err(convert_err_to_500(
+        "no free code after 5 tries",
+        Some("Pickup code generation err"),
+    ))
+}
+
+pub fn qr_payload(
+    order_id: i32,
+    code: &str,
+    collection_date: &DateTime<Utc>,
+) -> Result<String, ServiceError> {
+    let claims = PickupClaims::new(order_id, code, collection_day(collection_date));
+
+    encode_jwt(&claims).map_err(|err| convert_err_to_500(err, Some("Error creating QR payload")))
+}
@@ -1,14 +1,48 @@
-use actix_web::{put, web};
+use actix_web::{
+    delete, get,
+    http::header::{self, ContentType},
+    post, put, web, HttpResponse,
+};
+use chrono::{Duration, Local, Utc};
 use entity::{
     dinner, dinner_orders,
-    prelude::{Dinner, DinnerOrders},
+    model_enums::{Status, WebhookStatus},
+    no_show_policy, pickup_slot,
+    prelude::{
+        Dinner, DinnerOrders, ExtrasOrder, PickupSlot, User, UserDinnerOrders, WebhookEvent,
+    },
+    user, user_dinner_orders, webhook_event,
+};
+use sea_orm::{
+    prelude::Decimal, ActiveModelTrait, ColumnTrait, Condition, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
 };
-use sea_orm::{prelude::Decimal, ActiveEnum, ActiveModelTrait, EntityTrait, Set};
-use std::mem;
 
 use crate::{
-    appstate::AppState, errors::ServiceError, jwt_auth::AuthUser, map_db_err,
-    routes::structs::OrderStatusRequest, update_if_some,
+    appstate::AppState,
+    errors::ServiceError,
+    events::OrderEvent,
+    forecast::{forecast, forecast_csv, forecast_html},
+    jwt_auth::{decode_pickup_token, AuthUser},
+    map_db_err,
+    no_shows::current_policy,
+    order_status::{advance_to, change_status},
+    pagination::{Page, Pagination},
+    routes::{
+        order::{cancel_paid_order, filtered_orders, line_response, PENDING_STATUSES},
+        structs::{
+            AdjustBalanceRequest, BalanceAdjusted, BulkStatusRequest, BulkStatusResponse,
+            BulkStatusResult, CancelRequest, CardRefund, CardRefundRequest, ForecastQuery,
+            NoShowPolicyRequest, NoShowUser, NoShowUsers, OrderCancelled, OrderFilter,
+            OrderStatusRequest, RedeemRequest, RedeemedOrder, ReportFormat, SlotRequest,
+            WebhookEventFilter, WebhookEventResponse, WebhookEvents, WebhookReplayed,
+        },
+    },
+    update_if_some, wallet, webhooks,
 };
 
 use super::structs::UpdateMenu;
@@ -66,21 +100,772 @@ async fn change_order_status(
         ));
     }
 
+    let body = body.into_inner();
+    //cancelling has to refund the wallet and release stock, no-shows have to be counted
+    match body.new_status {
+        Status::Cancelled => {
+            return Err(ServiceError::BadRequest(
+                "Use the cancel endpoint to cancel an order".into(),
+            ))
+        }
+        Status::NoShow => {
+            return Err(ServiceError::BadRequest(
+                "Orders become no-shows when they aren't collected".into(),
+            ))
+        }
+        _ => {}
+    }
+
     let conn = &data.conn;
     let claim_id = path.into_inner();
 
-    let mut order: dinner_orders::ActiveModel = {
-        let order = DinnerOrders::find_by_id(claim_id)
-            .one(conn)
-            .await
-            .map_err(map_db_err)?;
-        let Some(order) = order else {return Err(ServiceError::BadRequest("Invalid dinner_order id".into()))};
-        order.into()
+    let txn = conn.begin().await.map_err(map_db_err)?;
+    let order = DinnerOrders::find_by_id(claim_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(map_db_err)?;
+    let Some(order) = order else {return Err(ServiceError::BadRequest("Invalid dinner_order id".into()))};
+
+    let from = Status::from_repr(order.status).ok_or(ServiceError::InternalError)?;
+    let order = change_status(&txn, order, body.new_status, Some(user.id), body.note).await?;
+    txn.commit().await.map_err(map_db_err)?;
+
+    data.events.publish(OrderEvent::StatusChanged {
+        order_id: order.id,
+        user_id: order.user_id,
+        from,
+        to: body.new_status,
+    });
+
+    Ok("Success".into())
+}
+
+#[delete("/{id}")]
+async fn admin_cancel_order(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+    body: Option<web::Json<CancelRequest>>,
+) -> Result<web::Json<OrderCancelled>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let reason = body.and_then(|body| body.into_inner().reason);
+
+    cancel_paid_order(&data, path.into_inner(), &user, true, reason)
+        .await
+        .map(web::Json)
+}
+
+//most orders one bulk request may touch
+const BULK_LIMIT: usize = 500;
+
+//Same transition rules as change_order_status applied to many orders at once.
+//Orders the rules don't allow are reported and skipped, the rest is committed together
+#[post("/status")]
+async fn bulk_change_order_status(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    body: web::Json<BulkStatusRequest>,
+) -> Result<web::Json<BulkStatusResponse>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let body = body.into_inner();
+    //cancelling has to refund the wallet, that's what the cancel endpoint is for
+    if body.new_status == Status::Cancelled {
+        return Err(ServiceError::BadRequest(
+            "Orders can't be cancelled in bulk, use the cancel endpoint".into(),
+        ));
+    }
+    if body.new_status == Status::NoShow {
+        return Err(ServiceError::BadRequest(
+            "Orders become no-shows when they aren't collected".into(),
+        ));
+    }
+    if body.note.as_ref().is_some_and(|n| n.chars().count() > 255) {
+        return Err(ServiceError::BadRequest(
+            "Status note can't be longer than 255 characters".into(),
+        ));
+    }
+
+    let conn = &data.conn;
+    let txn = conn.begin().await.map_err(map_db_err)?;
+    let (requested, orders) = match (body.order_ids, body.filter) {
+        (Some(ids), None) => {
+            if ids.is_empty() || ids.len() > BULK_LIMIT {
+                return Err(ServiceError::BadRequest(format!(
+                    "Between 1 and {} orders can be changed at once",
+                    BULK_LIMIT
+                )));
+            }
+            let orders = DinnerOrders::find()
+                .filter(dinner_orders::Column::Id.is_in(ids.clone()))
+                .order_by_asc(dinner_orders::Column::Id)
+                .lock_exclusive()
+                .all(&txn)
+                .await
+                .map_err(map_db_err)?;
+            (ids, orders)
+        }
+        (None, Some(filter)) => {
+            let order_filter = OrderFilter {
+                status: filter.status,
+                from: Some(filter.day),
+                to: Some(filter.day),
+                dinner_id: filter.dinner_id,
+                ..Default::default()
+            };
+            let mut select = filtered_orders(&order_filter, &PENDING_STATUSES);
+            if let Some(slot_id) = filter.slot_id {
+                select = select.filter(dinner_orders::Column::SlotId.eq(slot_id));
+            }
+            let orders = select
+                .limit(BULK_LIMIT as u64 + 1)
+                .lock_exclusive()
+                .all(&txn)
+                .await
+                .map_err(map_db_err)?;
+            if orders.len() > BULK_LIMIT {
+                return Err(ServiceError::BadRequest(format!(
+                    "Filter matches more than {} orders",
+                    BULK_LIMIT
+                )));
+            }
+            (orders.iter().map(|o| o.id).collect(), orders)
+        }
+        _ => {
+            return Err(ServiceError::BadRequest(
+                "Either orderIds or filter has to be given".into(),
+            ))
+        }
     };
 
-    //MySQL has no bools
-    order.status = Set(body.into_inner().new_status.into_value());
-    order.update(conn).await.map_err(map_db_err)?;
+    let mut orders: HashMap<_, _> = orders.into_iter().map(|o| (o.id, o)).collect();
+    let mut seen = HashSet::new();
+    let mut results = Vec::new();
+    let mut changed = Vec::new();
+    for order_id in requested {
+        if !seen.insert(order_id) {
+            continue;
+        }
+        let Some(order) = orders.remove(&order_id) else {
+            results.push(BulkStatusResult {
+                order_id,
+                ok: false,
+                from: None,
+                error: Some("No order has given id".into()),
+            });
+            continue;
+        };
+
+        let from = Status::from_repr(order.status).ok_or(ServiceError::InternalError)?;
+        let note = body.note.clone();
+        match change_status(&txn, order, body.new_status, Some(user.id), note).await {
+            Ok(order) => {
+                changed.push(OrderEvent::StatusChanged {
+                    order_id,
+                    user_id: order.user_id,
+                    from,
+                    to: body.new_status,
+                });
+                results.push(BulkStatusResult {
+                    order_id,
+                    ok: true,
+                    from: Some(from),
+                    error: None,
+                });
+            }
+            //transition not allowed for this one, the others still go through
+            Err(ServiceError::Conflict(msg)) => results.push(BulkStatusResult {
+                order_id,
+                ok: false,
+                from: Some(from),
+                error: Some(msg),
+            }),
+            Err(err) => return Err(err),
+        }
+    }
+    txn.commit().await.map_err(map_db_err)?;
+
+    let changed_count = changed.len();
+    for event in changed {
+        data.events.publish(event);
+    }
+
+    Ok(web::Json(BulkStatusResponse {
+        changed: changed_count,
+        failed: results.len() - changed_count,
+        results,
+    }))
+}
+
+//Hands out everything a scanned QR code stands for, the whole order or one line of a group order
+pub async fn redeem_pickup(
+    data: &AppState,
+    admin_id: i32,
+    payload: &str,
+) -> Result<RedeemedOrder, ServiceError> {
+    let claims = decode_pickup_token(payload)?;
+    if claims.day != Local::now().date_naive() {
+        return Err(ServiceError::BadRequest(format!(
+            "Order is meant to be collected on {}",
+            claims.day
+        )));
+    }
+
+    let conn = &data.conn;
+    let txn = conn.begin().await.map_err(map_db_err)?;
+    let order = DinnerOrders::find_by_id(claims.order_id()?)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(map_db_err)?;
+    let Some(order) = order else {return Err(ServiceError::NotFound("No order has given id".into()))};
+
+    let from = Status::from_repr(order.status).ok_or(ServiceError::InternalError)?;
+    if from == Status::Collected {
+        return Err(ServiceError::Conflict("Order was already collected".into()));
+    }
+    //cancelled orders were refunded, no line of them may be handed out
+    if !PENDING_STATUSES.contains(&from) {
+        return Err(ServiceError::Conflict(format!(
+            "Order can't be collected, it is {:?}",
+            from
+        )));
+    }
+
+    let mut lines = UserDinnerOrders::find()
+        .filter(user_dinner_orders::Column::OrderId.eq(order.id))
+        .find_with_related(ExtrasOrder)
+        .all(&txn)
+        .await
+        .map_err(map_db_err)?;
+
+    //order's own code hands out everything, a group line's code only that person's dish
+    let now = Utc::now();
+    let redeemed_line = if order.pickup_code.as_deref() == Some(claims.code.as_str()) {
+        None
+    } else {
+        let Some((line, _)) = lines
+            .iter()
+            .find(|(line, _)| line.pickup_code.as_deref() == Some(claims.code.as_str()))
+        else {
+            return Err(ServiceError::BadRequest(
+                "Pickup code doesn't match the order".into(),
+            ));
+        };
+        if line.collected_at.is_some() {
+            return Err(ServiceError::Conflict("Dish was already collected".into()));
+        }
+        Some(line.id)
+    };
+
+    for (line, _) in lines.iter_mut() {
+        let collect = line.pickup_code.is_some()
+            && line.collected_at.is_none()
+            && redeemed_line.is_none_or(|id| id == line.id);
+        if collect {
+            let mut active: user_dinner_orders::ActiveModel = line.clone().into();
+            active.collected_at = Set(Some(now));
+            *line = active.update(&txn).await.map_err(map_db_err)?;
+        }
+    }
+
+    //group order counts as collected once the last person got their dish
+    let everything_collected = lines
+        .iter()
+        .all(|(line, _)| line.pickup_code.is_none() || line.collected_at.is_some());
+    let order = if redeemed_line.is_none() || everything_collected {
+        advance_to(
+            &txn,
+            order,
+            Status::Collected,
+            Some(admin_id),
+            Some("Redeemed at the counter".into()),
+        )
+        .await?
+    } else {
+        order
+    };
+
+    let dinners = lines
+        .into_iter()
+        .filter(|(line, _)| redeemed_line.is_none_or(|id| id == line.id))
+        .map(|(line, extras)| {
+            line_response(line, extras.into_iter().map(|e| e.extras_id).collect())
+        })
+        .collect();
+    txn.commit().await.map_err(map_db_err)?;
+
+    let to = Status::from_repr(order.status).ok_or(ServiceError::InternalError)?;
+    if to != from {
+        data.events.publish(OrderEvent::StatusChanged {
+            order_id: order.id,
+            user_id: order.user_id,
+            from,
+            to,
+        });
+    }
+
+    Ok(RedeemedOrder {
+        order_id: order.id,
+        user_id: order.user_id,
+        pickup_code: claims.code,
+        dinners,
+    })
+}
+
+//cashier scans QR from student's app and hands out the meal in one step
+#[post("/redeem")]
+async fn redeem_order(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    body: web::Json<RedeemRequest>,
+) -> Result<web::Json<RedeemedOrder>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    redeem_pickup(&data, user.id, &body.payload)
+        .await
+        .map(web::Json)
+}
+
+fn validate_slot(slot: &SlotRequest) -> Result<(), ServiceError> {
+    if slot.start_time >= slot.end_time {
+        return Err(ServiceError::BadRequest(
+            "Slot has to start before it ends".into(),
+        ));
+    }
+    if slot.capacity < 0 {
+        return Err(ServiceError::BadRequest(
+            "Slot capacity can't be negative".into(),
+        ));
+    }
+    Ok(())
+}
+
+//all slots, inactive included
+#[get("/")]
+async fn get_slots_admin(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<web::Json<Vec<pickup_slot::Model>>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let slots = PickupSlot::find()
+        .order_by_asc(pickup_slot::Column::WeekDay)
+        .order_by_asc(pickup_slot::Column::StartTime)
+        .all(&data.conn)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(web::Json(slots))
+}
+
+#[post("/")]
+async fn create_slot(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    body: web::Json<SlotRequest>,
+) -> Result<web::Json<pickup_slot::Model>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let body = body.into_inner();
+    validate_slot(&body)?;
+
+    let slot = pickup_slot::ActiveModel {
+        week_day: Set(body.week_day as u8),
+        start_time: Set(body.start_time),
+        end_time: Set(body.end_time),
+        capacity: Set(body.capacity),
+        active: Set(1),
+        ..Default::default()
+    }
+    .insert(&data.conn)
+    .await
+    .map_err(map_db_err)?;
+
+    Ok(web::Json(slot))
+}
+
+#[put("/{id}")]
+async fn update_slot(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+    body: web::Json<SlotRequest>,
+) -> Result<web::Json<pickup_slot::Model>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let body = body.into_inner();
+    validate_slot(&body)?;
+
+    let conn = &data.conn;
+    let slot = PickupSlot::find_by_id(path.into_inner())
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    let Some(slot) = slot else {return Err(ServiceError::NotFound("No pickup slot has given id".into()))};
+
+    //orders already placed keep their collection date, only new ones see the change
+    let mut slot: pickup_slot::ActiveModel = slot.into();
+    slot.week_day = Set(body.week_day as u8);
+    slot.start_time = Set(body.start_time);
+    slot.end_time = Set(body.end_time);
+    slot.capacity = Set(body.capacity);
+    slot.active = Set(1);
+    let slot = slot.update(conn).await.map_err(map_db_err)?;
+
+    Ok(web::Json(slot))
+}
+
+//slots are only deactivated, existing orders still point at them
+#[delete("/{id}")]
+async fn deactivate_slot(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+) -> Result<String, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let conn = &data.conn;
+    let slot = PickupSlot::find_by_id(path.into_inner())
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    let Some(slot) = slot else {return Err(ServiceError::NotFound("No pickup slot has given id".into()))};
+
+    let mut slot: pickup_slot::ActiveModel = slot.into();
+    slot.active = Set(0);
+    slot.update(conn).await.map_err(map_db_err)?;
 
     Ok("Success".into())
 }
+
+#[get("/policy")]
+async fn get_no_show_policy(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<web::Json<no_show_policy::Model>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    current_policy(&data.conn).await.map(web::Json)
+}
+
+#[put("/policy")]
+async fn update_no_show_policy(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    body: web::Json<NoShowPolicyRequest>,
+) -> Result<web::Json<no_show_policy::Model>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let body = body.into_inner();
+    if body.expire_after_minutes < 0 || body.warn_after < 0 || body.block_after < 0 {
+        return Err(ServiceError::BadRequest(
+            "No-show policy values can't be negative".into(),
+        ));
+    }
+    if body.block_after > 0 && body.block_days <= 0 {
+        return Err(ServiceError::BadRequest(
+            "Block has to last at least one day".into(),
+        ));
+    }
+
+    let conn = &data.conn;
+    let mut policy: no_show_policy::ActiveModel = current_policy(conn).await?.into();
+    policy.expire_after_minutes = Set(body.expire_after_minutes);
+    policy.warn_after = Set(body.warn_after);
+    policy.block_after = Set(body.block_after);
+    policy.block_days = Set(body.block_days);
+    let policy = policy.update(conn).await.map_err(map_db_err)?;
+
+    Ok(web::Json(policy))
+}
+
+//users with at least one no-show or a running block, worst first
+#[get("/")]
+async fn get_no_show_users(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    pagination: Pagination,
+) -> Result<web::Json<Page<NoShowUsers>>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let select = User::find()
+        .filter(
+            Condition::any()
+                .add(user::Column::NoShows.gt(0))
+                .add(user::Column::BlockedUntil.gt(Utc::now())),
+        )
+        .order_by_desc(user::Column::NoShows)
+        .order_by_asc(user::Column::Id);
+    let (users, total) = pagination.fetch(&data.conn, select).await?;
+
+    let users = users
+        .into_iter()
+        .map(|u| NoShowUser {
+            user_id: u.id,
+            username: u.username,
+            email: u.email,
+            no_shows: u.no_shows,
+            blocked_until: u.blocked_until,
+        })
+        .collect();
+    Ok(web::Json(pagination.page_of(NoShowUsers { users }, total)))
+}
+
+//forgives user's no-shows and lifts the block
+#[post("/{id}/reset")]
+async fn reset_no_shows(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+) -> Result<String, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let conn = &data.conn;
+    let found = User::find_by_id(path.into_inner())
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    let Some(found) = found else {return Err(ServiceError::NotFound("No user has given id".into()))};
+
+    let mut found: user::ActiveModel = found.into();
+    found.no_shows = Set(0);
+    found.blocked_until = Set(None);
+    found.update(conn).await.map_err(map_db_err)?;
+
+    Ok("Success".into())
+}
+
+//longest range the forecast can cover at once
+const FORECAST_MAX_DAYS: i64 = 31;
+
+//portions the kitchen has to prepare for each day, defaults to the coming week
+#[get("/forecast")]
+async fn get_forecast(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    query: web::Query<ForecastQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let query = query.into_inner();
+    let from = query.from.unwrap_or_else(|| Local::now().date_naive());
+    let to = query.to.unwrap_or(from + Duration::days(6));
+    if to < from {
+        return Err(ServiceError::BadRequest(
+            "End of the range can't be before its start".into(),
+        ));
+    }
+    if (to - from).num_days() >= FORECAST_MAX_DAYS {
+        return Err(ServiceError::BadRequest(format!(
+            "Forecast can cover at most {} days",
+            FORECAST_MAX_DAYS
+        )));
+    }
+
+    let report = forecast(&data.conn, from, to, query.by_slot).await?;
+    Ok(match query.format {
+        ReportFormat::Json => HttpResponse::Ok().json(report),
+        ReportFormat::Csv => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"forecast_{}_{}.csv\"", from, to),
+            ))
+            .body(forecast_csv(&report)),
+        ReportFormat::Html => HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(forecast_html(&report)),
+    })
+}
+
+//manual wallet correction, e.g. cash handed over at the counter or a goodwill refund
+#[post("/{id}/adjust")]
+async fn adjust_balance(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+    body: web::Json<AdjustBalanceRequest>,
+) -> Result<web::Json<BalanceAdjusted>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let body = body.into_inner();
+    let note = body.note.trim().to_string();
+    if body.amount == 0 {
+        return Err(ServiceError::BadRequest("Amount can't be 0".into()));
+    }
+    if note.is_empty() || note.chars().count() > 255 {
+        return Err(ServiceError::BadRequest(
+            "Note has to be between 1 and 255 characters".into(),
+        ));
+    }
+
+    let conn = &data.conn;
+    let user_id = path.into_inner();
+    let found = User::find_by_id(user_id)
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    if found.is_none() {
+        return Err(ServiceError::NotFound("No user has given id".into()));
+    }
+
+    let txn = conn.begin().await.map_err(map_db_err)?;
+    let balance = wallet::adjust(&txn, user_id, body.amount, user.id, note).await?;
+    txn.commit().await.map_err(map_db_err)?;
+
+    Ok(web::Json(BalanceAdjusted {
+        user_id,
+        amount: body.amount,
+        balance,
+    }))
+}
+
+//refunds user's unspent balance to their cards, e.g. when they leave the school
+#[post("/{id}/refund")]
+async fn admin_refund_balance(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+    body: web::Json<CardRefundRequest>,
+) -> Result<web::Json<CardRefund>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let conn = &data.conn;
+    let user_id = path.into_inner();
+    let found = User::find_by_id(user_id)
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    if found.is_none() {
+        return Err(ServiceError::NotFound("No user has given id".into()));
+    }
+
+    let refund = wallet::refund_to_card(
+        conn,
+        data.payments.as_ref(),
+        user_id,
+        body.amount,
+        Some(user.id),
+    )
+    .await?;
+    Ok(web::Json(refund))
+}
+
+//stored payment provider deliveries newest first, `?status=Failed` for the ones to look at
+#[get("/")]
+async fn get_webhook_events(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    pagination: Pagination,
+    filter: web::Query<WebhookEventFilter>,
+) -> Result<web::Json<Page<WebhookEvents>>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let mut select = WebhookEvent::find().order_by_desc(webhook_event::Column::Id);
+    if let Some(statuses) = &filter.status {
+        select = select.filter(
+            webhook_event::Column::Status.is_in(statuses.iter().map(|status| *status as u8)),
+        );
+    }
+    let (events, total) = pagination.fetch(&data.conn, select).await?;
+
+    let events = events
+        .into_iter()
+        .map(|event| {
+            Ok(WebhookEventResponse {
+                id: event.id,
+                status: WebhookStatus::from_repr(event.status)
+                    .ok_or(ServiceError::InternalError)?,
+                event_id: event.event_id,
+                event_type: event.event_type,
+                attempts: event.attempts,
+                error: event.error,
+                received_at: event.received_at,
+                processed_at: event.processed_at,
+            })
+        })
+        .collect::<Result<Vec<_>, ServiceError>>()?;
+    Ok(web::Json(
+        pagination.page_of(WebhookEvents { events }, total),
+    ))
+}
+
+#[post("/{id}/replay")]
+async fn replay_webhook_event(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+) -> Result<web::Json<WebhookReplayed>, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "You need to be an admin to access this".into(),
+        ));
+    }
+
+    let id = path.into_inner();
+    let status = webhooks::replay(&data.conn, data.payments.as_ref(), id).await?;
+    Ok(web::Json(WebhookReplayed { id, status }))
+}
@@ -0,0 +1,409 @@
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{delete, get, post, web, HttpResponse};
+use chrono::{Datelike, NaiveDate, Utc};
+use entity::{
+    dinner, dinner_orders, extras, extras_dinner, extras_order, favourite, favourite_dinner,
+    favourite_extras, user_dinner_orders,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait,
+};
+
+use crate::{
+    appstate::AppState,
+    errors::ServiceError,
+    idempotency::Idempotency,
+    jwt_auth::AuthUser,
+    map_db_err,
+    routes::{
+        order::{line_response, place_order},
+        structs::{
+            Dinner, DinnerResponse, FavouriteRequest, FavouriteResponse, OrderRequest,
+            ReorderRequest, Reordered, SkippedLine,
+        },
+    },
+};
+
+//dishes are matched across weeks by name, the scraper inserts a fresh row every menu update
+pub fn dish_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+//Maps lines of an old order onto the menu of `day`, every dish and extra is looked up by name.
+//Returns lines ready for a new OrderRequest and everything that had to be left out
+async fn carry_over<C>(
+    conn: &C,
+    lines: &[DinnerResponse],
+    day: NaiveDate,
+) -> Result<(Vec<Dinner>, Vec<SkippedLine>), ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let old_dinners: HashMap<_, _> = dinner::Entity::find()
+        .filter(dinner::Column::Id.is_in(lines.iter().map(|l| l.dinner_id)))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|dinner| (dinner.id, dinner))
+        .collect();
+    let old_extras: HashMap<_, _> = extras::Entity::find()
+        .filter(extras::Column::Id.is_in(lines.iter().flat_map(|l| l.extras_ids.iter().copied())))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|extra| (extra.id, extra))
+        .collect();
+
+    //ascending ids so the newest dish with given name wins
+    let week_day = day.weekday().num_days_from_monday() as u8;
+    let menu: HashMap<_, _> = dinner::Entity::find()
+        .filter(dinner::Column::WeekDay.eq(week_day))
+        .order_by_asc(dinner::Column::Id)
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|dinner| (dish_key(&dinner.name), dinner))
+        .collect();
+
+    let menu_links = extras_dinner::Entity::find()
+        .filter(extras_dinner::Column::DinnerId.is_in(menu.values().map(|d| d.id)))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+    let menu_extras: HashMap<_, _> = extras::Entity::find()
+        .filter(extras::Column::Id.is_in(menu_links.iter().map(|l| l.extras_id)))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|extra| (extra.id, extra))
+        .collect();
+    let mut offered: HashMap<(i32, String), i32> = HashMap::new();
+    for link in menu_links {
+        if let Some(extra) = menu_extras.get(&link.extras_id) {
+            offered.insert((link.dinner_id, dish_key(&extra.name)), extra.id);
+        }
+    }
+
+    let mut carried = Vec::new();
+    let mut skipped = Vec::new();
+    let mut used = HashSet::new();
+    for line in lines {
+        let Some(old) = old_dinners.get(&line.dinner_id) else {
+            skipped.push(SkippedLine {
+                dinner_id: line.dinner_id,
+                extras_id: None,
+                name: String::new(),
+                reason: "Dish no longer exists".into(),
+            });
+            continue;
+        };
+        let Some(new) = menu.get(&dish_key(&old.name)) else {
+            skipped.push(SkippedLine {
+                dinner_id: old.id,
+                extras_id: None,
+                name: old.name.clone(),
+                reason: format!("Not served on {}", day),
+            });
+            continue;
+        };
+        if !used.insert(new.id) {
+            skipped.push(SkippedLine {
+                dinner_id: old.id,
+                extras_id: None,
+                name: old.name.clone(),
+                reason: "Dish is already in the order".into(),
+            });
+            continue;
+        }
+
+        let mut extras_ids = Vec::new();
+        for extras_id in line.extras_ids.iter() {
+            let extra = old_extras.get(extras_id);
+            let new_extra = extra.and_then(|e| offered.get(&(new.id, dish_key(&e.name))));
+            match new_extra {
+                Some(id) if !extras_ids.contains(id) => extras_ids.push(*id),
+                _ => skipped.push(SkippedLine {
+                    dinner_id: old.id,
+                    extras_id: Some(*extras_id),
+                    name: extra.map(|e| e.name.clone()).unwrap_or_default(),
+                    reason: format!("Not served with {} on {}", new.name, day),
+                }),
+            }
+        }
+
+        //kitchen requests go along with the dish
+        carried.push(Dinner {
+            dinner_id: new.id,
+            extras_ids,
+            note: line.note.clone(),
+            dietary_flags: line.dietary_flags.clone(),
+            attendee_user_id: None,
+            attendee_name: None,
+        });
+    }
+
+    Ok((carried, skipped))
+}
+
+async fn reorder_lines(
+    data: &AppState,
+    user_id: i32,
+    lines: Vec<DinnerResponse>,
+    body: ReorderRequest,
+) -> Result<Reordered, ServiceError> {
+    let (dinners, skipped) = carry_over(&data.conn, &lines, body.collection_day).await?;
+    if dinners.is_empty() {
+        return Err(ServiceError::BadRequest(format!(
+            "None of the dishes is served on {}",
+            body.collection_day
+        )));
+    }
+
+    let new_lines = dinners.iter().map(DinnerResponse::from).collect();
+    let order = OrderRequest {
+        dinners,
+        collection_day: body.collection_day,
+        slot_id: body.slot_id,
+        note: None,
+    };
+    let order = place_order(data, user_id, order, false).await?;
+
+    Ok(Reordered {
+        order,
+        dinners: new_lines,
+        skipped,
+    })
+}
+
+async fn order_lines<C>(
+    conn: &C,
+    order_id: i32,
+    user_id: i32,
+) -> Result<Vec<DinnerResponse>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let order = dinner_orders::Entity::find_by_id(order_id)
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    if order.filter(|o| o.user_id == user_id).is_none() {
+        return Err(ServiceError::NotFound("No order has given id".into()));
+    }
+
+    Ok(user_dinner_orders::Entity::find()
+        .filter(user_dinner_orders::Column::OrderId.eq(order_id))
+        .order_by_asc(user_dinner_orders::Column::Id)
+        .find_with_related(extras_order::Entity)
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|(line, extras)| {
+            line_response(line, extras.into_iter().map(|e| e.extras_id).collect())
+        })
+        .collect())
+}
+
+//lines of given favourites keyed by favourite id
+async fn favourite_lines(
+    db: &DatabaseConnection,
+    favourite_ids: Vec<i32>,
+) -> Result<HashMap<i32, Vec<DinnerResponse>>, ServiceError> {
+    let lines = favourite_dinner::Entity::find()
+        .filter(favourite_dinner::Column::FavouriteId.is_in(favourite_ids))
+        .order_by_asc(favourite_dinner::Column::Id)
+        .find_with_related(favourite_extras::Entity)
+        .all(db)
+        .await
+        .map_err(map_db_err)?;
+
+    let mut output: HashMap<i32, Vec<DinnerResponse>> = HashMap::new();
+    for (line, extras) in lines {
+        output
+            .entry(line.favourite_id)
+            .or_default()
+            .push(DinnerResponse {
+                dinner_id: line.dinner_id,
+                extras_ids: extras.into_iter().map(|e| e.extras_id).collect(),
+                note: None,
+                dietary_flags: Vec::new(),
+                attendee_user_id: None,
+                attendee_name: None,
+                pickup_code: None,
+                collected: None,
+            });
+    }
+    Ok(output)
+}
+
+#[post("/")]
+async fn save_favourite(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    body: web::Json<FavouriteRequest>,
+) -> Result<web::Json<FavouriteResponse>, ServiceError> {
+    let body = body.into_inner();
+    let name = body.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(ServiceError::BadRequest(
+            "Favourite name has to be between 1 and 64 characters".into(),
+        ));
+    }
+
+    let db = &data.conn;
+    let lines = order_lines(db, body.order_id, user.id).await?;
+
+    let txn = db.begin().await.map_err(map_db_err)?;
+    let saved = favourite::ActiveModel {
+        user_id: Set(user.id),
+        name: Set(name),
+        created_at: Set(Utc::now()),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await
+    .map_err(map_db_err)?;
+
+    for line in lines.iter() {
+        let line_id = favourite_dinner::Entity::insert(favourite_dinner::ActiveModel {
+            favourite_id: Set(saved.id),
+            dinner_id: Set(line.dinner_id),
+            ..Default::default()
+        })
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?
+        .last_insert_id;
+
+        let extras = line
+            .extras_ids
+            .iter()
+            .map(|extras_id| favourite_extras::ActiveModel {
+                favourite_dinner_id: Set(line_id),
+                extras_id: Set(*extras_id),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        if !extras.is_empty() {
+            favourite_extras::Entity::insert_many(extras)
+                .exec(&txn)
+                .await
+                .map_err(map_db_err)?;
+        }
+    }
+    txn.commit().await.map_err(map_db_err)?;
+
+    Ok(web::Json(FavouriteResponse {
+        favourite_id: saved.id,
+        name: saved.name,
+        created_at: saved.created_at,
+        dinners: lines,
+    }))
+}
+
+#[get("/")]
+async fn get_favourites(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<web::Json<Vec<FavouriteResponse>>, ServiceError> {
+    let db = &data.conn;
+    let favourites = favourite::Entity::find()
+        .filter(favourite::Column::UserId.eq(user.id))
+        .order_by_asc(favourite::Column::Name)
+        .all(db)
+        .await
+        .map_err(map_db_err)?;
+    let mut lines = favourite_lines(db, favourites.iter().map(|f| f.id).collect()).await?;
+
+    Ok(web::Json(
+        favourites
+            .into_iter()
+            .map(|f| FavouriteResponse {
+                dinners: lines.remove(&f.id).unwrap_or_default(),
+                favourite_id: f.id,
+                name: f.name,
+                created_at: f.created_at,
+            })
+            .collect(),
+    ))
+}
+
+#[delete("/{id}")]
+async fn delete_favourite(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+) -> Result<String, ServiceError> {
+    let res = favourite::Entity::delete_many()
+        .filter(favourite::Column::Id.eq(path.into_inner()))
+        .filter(favourite::Column::UserId.eq(user.id))
+        .exec(&data.conn)
+        .await
+        .map_err(map_db_err)?;
+
+    if res.rows_affected == 0 {
+        return Err(ServiceError::NotFound("No favourite has given id".into()));
+    }
+    Ok("Success".into())
+}
+
+#[post("/{id}/order")]
+async fn order_favourite(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+    idempotency: Idempotency,
+    body: web::Json<ReorderRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let favourite_id = path.into_inner();
+    let favourite = favourite::Entity::find_by_id(favourite_id)
+        .one(&data.conn)
+        .await
+        .map_err(map_db_err)?;
+    if favourite.filter(|f| f.user_id == user.id).is_none() {
+        return Err(ServiceError::NotFound("No favourite has given id".into()));
+    }
+
+    let lines = favourite_lines(&data.conn, vec![favourite_id])
+        .await?
+        .remove(&favourite_id)
+        .unwrap_or_default();
+
+    let body = body.into_inner();
+    idempotency
+        .with_body(&body)?
+        .respond(
+            &data.conn,
+            user.id,
+            reorder_lines(&data, user.id, lines, body),
+        )
+        .await
+}
+
+#[post("/{id}/reorder")]
+async fn reorder(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+    idempotency: Idempotency,
+    body: web::Json<ReorderRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let lines = order_lines(&data.conn, path.into_inner(), user.id).await?;
+
+    let body = body.into_inner();
+    idempotency
+        .with_body(&body)?
+        .respond(
+            &data.conn,
+            user.id,
+            reorder_lines(&data, user.id, lines, body),
+        )
+        .await
+}
@@ -0,0 +1,41 @@
+use actix_web::{get, http::header, web, HttpResponse};
+
+use crate::{appstate::AppState, errors::ServiceError, events::sse_stream, jwt_auth::AuthUser};
+
+//kitchen display, every order created or changed by anyone
+#[get("/feed")]
+async fn admin_order_feed(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ServiceError> {
+    if !user.is_admin {
+        return Err(ServiceError::Unauthorized(
+            "Only admin can access that data".to_string(),
+        ));
+    }
+
+    let events = sse_stream(data.events.subscribe(), |_| true);
+
+    Ok(sse_response().streaming(events))
+}
+
+//changes of user's own orders, e.g. order being ready for pickup
+#[get("/feed")]
+async fn user_order_feed(user: AuthUser, data: web::Data<AppState>) -> HttpResponse {
+    let user_id = user.id;
+    let events = sse_stream(data.events.subscribe(), move |event| {
+        event.user_id() == user_id
+    });
+
+    sse_response().streaming(events)
+}
+
+fn sse_response() -> actix_web::HttpResponseBuilder {
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        //stops reverse proxies from buffering the stream
+        .insert_header(("X-Accel-Buffering", "no"));
+    response
+}
@@ -2,7 +2,7 @@ use std::{collections::HashSet, mem};
 
 use crate::{jwt_auth::AuthUser, routes::structs::{MenuResult3D, LastUpdateResponse}};
 use actix_web::{get, web, Responder};
-use chrono::{DateTime, Datelike, Utc};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
 use entity::{
     custom_impl::DinnerToExtras,
     dinner, menu_info,
@@ -16,15 +16,41 @@ use sea_orm::{
 use crate::{
     appstate::AppState,
     errors::ServiceError,
+    jobs::run_subscriptions,
     map_db_err,
     routes::structs::MenuOneDay,
     scraper::{scrape_menu, update_menu},
+    slots::{slot_usage, slots_for_day},
+    stock::{date_for_week_day, reserved_portions},
 };
 
-use super::structs::DinnerWithExtras;
+use super::structs::{DinnerWithExtras, DinnerWithStock, SlotAvailability};
 
 type MenuResult = Result<web::Json<MenuOneDay>, ServiceError>;
 
+async fn with_stock(
+    conn: &DatabaseConnection,
+    dinners: Vec<dinner::Model>,
+) -> Result<Vec<DinnerWithStock>, ServiceError> {
+    let days: HashSet<_> = dinners
+        .iter()
+        .map(|dinner| date_for_week_day(dinner.week_day))
+        .collect();
+    let reserved = reserved_portions(conn, dinners.iter().map(|d| d.id), days).await?;
+
+    Ok(dinners
+        .into_iter()
+        .map(|dinner| {
+            let day = date_for_week_day(dinner.week_day);
+            let reserved = reserved.get(&(dinner.id, day)).copied().unwrap_or(0);
+            DinnerWithStock {
+                remaining: (dinner.max_supply - reserved).max(0),
+                dinner,
+            }
+        })
+        .collect())
+}
+
 async fn get_menu(conn: &DatabaseConnection, day: u8) -> MenuResult {
     let dinners = Dinner::find()
         .filter(dinner::Column::WeekDay.eq(day))
@@ -60,11 +86,13 @@ async fn get_menu(conn: &DatabaseConnection, day: u8) -> MenuResult {
     .map(|(dinner, extras)| (dinner.clone(), mem::take(extras)))
     .collect::<Vec<_>>(); */
 
+    let dinners = with_stock(conn, dinners).await?;
+
     Ok(web::Json(MenuOneDay { dinners, extras }))
 }
 
 async fn get_menu_3d(conn: &DatabaseConnection) -> Result<web::Json<MenuResult3D>, ServiceError> {
-    let mut dinners = Dinner::find()
+    let dinners = Dinner::find()
         .order_by(dinner::Column::WeekDay, migration::Order::Asc)
         .all(conn)
         .await
@@ -74,6 +102,7 @@ async fn get_menu_3d(conn: &DatabaseConnection) -> Result<web::Json<MenuResult3D
         .load_many_to_many(Extras, ExtrasDinner, conn)
         .await
         .map_err(map_db_err)?;
+    let mut dinners = with_stock(conn, dinners).await?;
 
     let mut result = MenuResult3D {
         response: vec![
@@ -86,10 +115,13 @@ async fn get_menu_3d(conn: &DatabaseConnection) -> Result<web::Json<MenuResult3D
         extras: HashSet::new(),
     };
     for (dinner, extras) in dinners.iter_mut().zip(extras.iter()) {
-        let index = dinner.week_day as usize;
+        let index = dinner.dinner.week_day as usize;
 
         result.extras.extend(extras.clone());
-        result.response[index].dinners.push(mem::take(dinner));
+        result.response[index].dinners.push(DinnerWithStock {
+            dinner: mem::take(&mut dinner.dinner),
+            remaining: dinner.remaining,
+        });
         if result.response[index].extras_ids.is_empty() {
             result.response[index].extras_ids = extras.iter().map(|x| x.id).collect();
         }
@@ -122,6 +154,34 @@ async fn get_menu_day(day: web::Path<u8>, data: web::Data<AppState>) -> MenuResu
     get_menu(&data.conn, day).await
 }
 
+#[get("/slots/{date}")]
+async fn get_slots(
+    date: web::Path<NaiveDate>,
+    data: web::Data<AppState>,
+) -> Result<web::Json<Vec<SlotAvailability>>, ServiceError> {
+    let day = date.into_inner();
+    let conn = &data.conn;
+
+    let slots = slots_for_day(conn, day).await?;
+    let usage = slot_usage(conn, day).await?;
+
+    Ok(web::Json(
+        slots
+            .into_iter()
+            .map(|slot| {
+                let taken = usage.get(&slot.id).copied().unwrap_or(0);
+                SlotAvailability {
+                    slot_id: slot.id,
+                    start_time: slot.start_time,
+                    end_time: slot.end_time,
+                    capacity: slot.capacity,
+                    remaining: (slot.capacity as i64 - taken).max(0) as i32,
+                }
+            })
+            .collect(),
+    ))
+}
+
 #[get("/last-update")]
 async fn last_menu_update(data: web::Data<AppState>) -> Result<impl Responder, ServiceError> {
     let date: DateTime<Utc> = menu_info::Entity::find()
@@ -146,5 +206,6 @@ async fn update(data: web::Data<AppState>, user: AuthUser) -> Result<String, Ser
 
     let menu = scrape_menu().await?;
     update_menu(&data.conn, menu).await?;
+    run_subscriptions(data);
     Ok("Success".into())
 }
@@ -1,6 +1,9 @@
 pub mod admin;
+pub mod favourites;
+pub mod feed;
 pub mod menu;
 pub mod order;
 pub mod payment;
 pub mod structs;
+pub mod subscriptions;
 pub mod users;
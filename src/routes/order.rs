@@ -1,113 +1,224 @@
-use std::{
-    collections::{HashMap, HashSet},
-    mem,
-};
+use std::collections::{HashMap, HashSet};
 
-use actix_web::{get, post, web};
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use chrono::{DateTime, Local, NaiveTime, Utc};
 use entity::{
-    dinner, dinner_orders, extras, extras_order, model_enums::Status, user, user_dinner_orders,
+    dinner, dinner_orders, extras, extras_order,
+    model_enums::{DietaryFlag, Status},
+    order_history, user, user_dinner_orders,
 };
-use rust_decimal::{prelude::ToPrimitive, Decimal};
 use sea_orm::{
-    ActiveEnum, ColumnTrait, DatabaseConnection, EntityTrait, LoaderTrait, QueryFilter,
-    QuerySelect, Set,
+    prelude::Decimal,
+    sea_query::{Order, Query},
+    ActiveEnum, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection,
+    EntityTrait, QueryFilter, QueryOrder, QuerySelect, Select, Set, TransactionTrait,
 };
 
 use crate::{
     appstate::AppState,
     convert_err_to_500,
     errors::ServiceError,
-    get_user,
+    events::OrderEvent,
+    idempotency::Idempotency,
     jwt_auth::AuthUser,
-    map_db_err, pay,
+    map_db_err,
+    no_shows::ensure_can_order,
+    order_status::{change_status, record_status},
+    pagination::{Page, Pagination},
+    pickup::{generate_pickup_code, qr_payload},
     routes::structs::{
-        AllUsersOrders, DinnerResponse, OrderRequest, OrderResponse, UserOrders, UserWithOrders,
+        AllUsersOrders, AttendeeCode, CancelRequest, Dinner, DinnerResponse, ModifyOrderRequest,
+        OrderCancelled, OrderCreated, OrderFilter, OrderModified, OrderRequest, OrderResponse,
+        OrderSort, SortDirection, StatusChange, UserOrders, UserWithOrders,
     },
+    slots::reserve_slot,
+    stock::{collection_day, day_bounds, release_stock, reserve_stock},
+    to_grosze,
+    validation::{clean_notes, validate_order, OrderContents},
+    wallet,
 };
 
-//TODO: TRANSACTIONS PROBABLY
+//safe to retry with the same Idempotency-Key, the order is placed only once
 #[post("/create")]
 async fn create_order(
     user: AuthUser,
     data: web::Data<AppState>,
+    idempotency: Idempotency,
     order: web::Json<OrderRequest>,
-) -> Result<String, ServiceError> {
-    let db = &data.conn;
+) -> Result<HttpResponse, ServiceError> {
     let order = order.into_inner();
-    let user_id = user.id;
+    idempotency
+        .with_body(&order)?
+        .respond(
+            &data.conn,
+            user.id,
+            place_order(&data, user.id, order, false),
+        )
+        .await
+}
 
+//one user orders for a whole class, lines may name who they're for
+//and each of them gets its own pickup code, everything is paid by the organiser
+#[post("/group")]
+async fn create_group_order(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    idempotency: Idempotency,
+    order: web::Json<OrderRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let order = order.into_inner();
+    idempotency
+        .with_body(&order)?
+        .respond(
+            &data.conn,
+            user.id,
+            place_order(&data, user.id, order, true),
+        )
+        .await
+}
+
+//Validates, reserves, stores and pays for a new order, shared by every way of ordering
+pub async fn place_order(
+    data: &AppState,
+    user_id: i32,
+    order: OrderRequest,
+    group: bool,
+) -> Result<OrderCreated, ServiceError> {
+    let db = &data.conn;
+    ensure_can_order(db, user_id).await?;
+    let mut order = order;
+    clean_notes(&mut order);
+    let contents = validate_order(db, &order, group).await?;
+    let price = contents.price(&order);
+    let total = to_grosze(price);
     let dinner_ids = order
         .dinners
         .iter()
         .map(|x| x.dinner_id)
         .collect::<Vec<_>>();
 
-    let extras_ids = order
-        .dinners
-        .iter()
-        .flat_map(|x| x.extras_ids.clone())
-        .collect::<Vec<_>>();
+    //everything below is a single unit - if any insert fails the transaction is dropped
+    //without commit which rolls the whole order back
+    let collection_date = contents.collection_date;
+    let note = order.note.clone();
+    let lines = order.dinners.iter().map(DinnerResponse::from).collect();
 
-    let dinners: Vec<(i32, Decimal)> = dinner::Entity::find()
-        .filter(dinner::Column::Id.is_in(dinner_ids.clone()))
-        .select_only()
-        .column(dinner::Column::Id)
-        .column(dinner::Column::Price)
-        .into_tuple()
-        .all(db)
-        .await
-        .map_err(map_db_err)?;
-    let extras: Vec<(i32, Decimal)> = extras::Entity::find()
-        .filter(extras::Column::Id.is_in(extras_ids.clone()))
-        .select_only()
-        .column(extras::Column::Id)
-        .column(extras::Column::Price)
-        .into_tuple()
-        .all(db)
-        .await
-        .map_err(map_db_err)?;
-    let dinners: HashMap<_, _> = dinners.into_iter().collect();
-    let extras: HashMap<_, _> = extras.into_iter().collect();
+    let txn = db.begin().await.map_err(map_db_err)?;
+    reserve_slot(&txn, contents.slot.id, order.collection_day).await?;
+    reserve_stock(&txn, order.collection_day, &dinner_ids).await?;
+    let (order_id, pickup_code, attendees) =
+        insert_order(&txn, user_id, order, &contents, price, group).await?;
+    let qr_payload = qr_payload(order_id, &pickup_code, &collection_date)?;
 
-    let price: i64 = dinner_ids
-        .into_iter()
-        .map(|x| (dinners.get(&x).unwrap_or(&Decimal::ZERO).to_f64().unwrap() * 100f64) as i64)
-        .sum::<i64>()
-        + extras_ids
-            .into_iter()
-            .map(|x| (extras.get(&x).unwrap_or(&Decimal::ZERO).to_f64().unwrap() * 100f64) as i64)
-            .sum::<i64>();
+    //charged in the same transaction, a failed commit takes nothing from the wallet
+    let balance = wallet::pay(&txn, user_id, total, order_id).await?;
+    txn.commit().await.map_err(map_db_err)?;
 
-    // let customer = get_user(db, user.id, &data.stripe_client.0).await?;
-    // let balance = customer.balance.unwrap();
-    // if balance < price {
-    //     return Err(ServiceError::BadRequest("Not enough money".to_string()));
-    // }
+    data.events.publish(OrderEvent::OrderCreated {
+        order_id,
+        user_id,
+        collection_date,
+        note,
+        dinners: lines,
+    });
 
+    Ok(OrderCreated {
+        order_id,
+        total,
+        balance,
+        pickup_code,
+        qr_payload,
+        attendees,
+    })
+}
+
+async fn insert_order<C>(
+    conn: &C,
+    user_id: i32,
+    order: OrderRequest,
+    contents: &OrderContents,
+    price: Decimal,
+    group: bool,
+) -> Result<(i32, String, Vec<AttendeeCode>), ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let pickup_code = generate_pickup_code(conn, &contents.collection_date).await?;
     let dinner_order = dinner_orders::ActiveModel {
         user_id: Set(user_id),
-        collection_date: Set(order.collection_date),
+        collection_date: Set(contents.collection_date),
+        slot_id: Set(Some(contents.slot.id)),
         status: Set(Status::Paid.into_value()),
+        price: Set(price),
+        pickup_code: Set(Some(pickup_code.clone())),
+        note: Set(order.note),
+        is_group: Set(group as i8),
         ..Default::default()
     };
 
-    let dinner_order_res = dinner_orders::Entity::insert(dinner_order)
-        .exec(db)
+    let order_id = dinner_orders::Entity::insert(dinner_order)
+        .exec(conn)
         .await
-        .map_err(|e| convert_err_to_500(e, Some("Database error creating dinner_orders: {}")))?;
+        .map_err(|e| convert_err_to_500(e, Some("Database error creating dinner_orders")))?
+        .last_insert_id;
+    record_status(conn, order_id, None, Status::Paid, Some(user_id), None).await?;
+    let attendees = insert_lines(
+        conn,
+        order_id,
+        order.dinners,
+        &contents.collection_date,
+        group,
+    )
+    .await?;
+
+    Ok((order_id, pickup_code, attendees))
+}
+
+//Stores lines of an order with their extras, lines of group orders get their own pickup codes
+async fn insert_lines<C>(
+    conn: &C,
+    order_id: i32,
+    dinners: Vec<Dinner>,
+    collection_date: &DateTime<Utc>,
+    group: bool,
+) -> Result<Vec<AttendeeCode>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let mut attendees = Vec::new();
+    for dinner in dinners {
+        //codes of earlier lines are already visible inside the transaction
+        let line_code = if group {
+            Some(generate_pickup_code(conn, collection_date).await?)
+        } else {
+            None
+        };
+        if let Some(code) = &line_code {
+            attendees.push(AttendeeCode {
+                dinner_id: dinner.dinner_id,
+                attendee_user_id: dinner.attendee_user_id,
+                attendee_name: dinner.attendee_name.clone(),
+                pickup_code: code.clone(),
+                qr_payload: qr_payload(order_id, code, collection_date)?,
+            });
+        }
 
-    for dinner in order.dinners {
         let dinner_order_junction = user_dinner_orders::ActiveModel {
-            order_id: Set(dinner_order_res.last_insert_id),
+            order_id: Set(order_id),
             dinner_id: Set(dinner.dinner_id),
+            note: Set(dinner.note),
+            dietary_flags: Set(DietaryFlag::to_bits(&dinner.dietary_flags)),
+            attendee_user_id: Set(dinner.attendee_user_id),
+            attendee_name: Set(dinner.attendee_name),
+            pickup_code: Set(line_code),
             ..Default::default()
         };
 
         let dinner_order_res = user_dinner_orders::Entity::insert(dinner_order_junction)
-            .exec(db)
+            .exec(conn)
             .await
             .map_err(|e| {
-                convert_err_to_500(e, Some("Database error creating user_dinner_orders: {}"))
+                convert_err_to_500(e, Some("Database error creating user_dinner_orders"))
             })?;
 
         let vector = dinner
@@ -120,16 +231,286 @@ async fn create_order(
             })
             .collect::<Vec<_>>();
 
-        if !vector.is_empty(){
+        if !vector.is_empty() {
             extras_order::Entity::insert_many(vector)
-                .exec(db)
+                .exec(conn)
                 .await
-                .map_err(|e| convert_err_to_500(e, Some("Database error creating extras_order: {}")))?;
+                .map_err(|e| convert_err_to_500(e, Some("Database error creating extras_order")))?;
         }
     }
 
-    // pay(&data.stripe_client.0, db, user, price).await?;
-    Ok("Order created successfully".to_string())
+    Ok(attendees)
+}
+
+//time of day configured as HH:MM in ORDER_CUTOFF, checked once at startup
+pub fn cutoff_time() -> Result<NaiveTime, ServiceError> {
+    let cutoff = dotenvy::var("ORDER_CUTOFF").unwrap_or_else(|_| "09:00".into());
+    NaiveTime::parse_from_str(&cutoff, "%H:%M")
+        .map_err(|e| convert_err_to_500(e, Some("ORDER_CUTOFF must be HH:MM")))
+}
+
+//last moment (local time on the collection day) when user can still change their mind
+pub fn order_cutoff(collection_date: &DateTime<Utc>) -> Result<DateTime<Utc>, ServiceError> {
+    collection_day(collection_date)
+        .and_time(cutoff_time()?)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|cutoff| cutoff.with_timezone(&Utc))
+        .ok_or_else(|| {
+            convert_err_to_500(
+                "cutoff doesn't exist in local timezone",
+                Some("ORDER_CUTOFF"),
+            )
+        })
+}
+
+//Group order stays paid until its last line is handed out, a dish somebody already took
+//can't be refunded or swapped
+fn reject_collected(lines: &[user_dinner_orders::Model], action: &str) -> Result<(), ServiceError> {
+    if lines.iter().any(|line| line.collected_at.is_some()) {
+        return Err(ServiceError::BadRequest(format!(
+            "Order can't be {} after some of its dishes were collected",
+            action
+        )));
+    }
+    Ok(())
+}
+
+//Cancels order that wasn't prepared yet, gives back its stock and refunds its price to the wallet.
+//`as_admin` skips the ownership and cutoff checks
+pub async fn cancel_paid_order(
+    data: &AppState,
+    order_id: i32,
+    user: &AuthUser,
+    as_admin: bool,
+    reason: Option<String>,
+) -> Result<OrderCancelled, ServiceError> {
+    let db = &data.conn;
+
+    if reason.as_ref().is_some_and(|r| r.chars().count() > 255) {
+        return Err(ServiceError::BadRequest(
+            "Cancellation reason can't be longer than 255 characters".into(),
+        ));
+    }
+
+    let txn = db.begin().await.map_err(map_db_err)?;
+    let order = dinner_orders::Entity::find_by_id(order_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(map_db_err)?;
+    let Some(order) = order.filter(|o| as_admin || o.user_id == user.id) else {
+        return Err(ServiceError::NotFound("No order has given id".into()));
+    };
+
+    let cutoff = order_cutoff(&order.collection_date)?;
+    if !as_admin && Utc::now() >= cutoff {
+        return Err(ServiceError::BadRequest(format!(
+            "Order could only be cancelled before {}",
+            cutoff.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        )));
+    }
+
+    let lines = user_dinner_orders::Entity::find()
+        .filter(user_dinner_orders::Column::OrderId.eq(order.id))
+        .all(&txn)
+        .await
+        .map_err(map_db_err)?;
+    reject_collected(&lines, "cancelled")?;
+    let dinner_ids = lines.iter().map(|l| l.dinner_id).collect::<Vec<_>>();
+    release_stock(&txn, collection_day(&order.collection_date), &dinner_ids).await?;
+
+    let refunded = to_grosze(order.price);
+    let owner_id = order.user_id;
+    let order = change_status(
+        &txn,
+        order,
+        Status::Cancelled,
+        Some(user.id),
+        reason.clone(),
+    )
+    .await?;
+    let mut order: dinner_orders::ActiveModel = order.into();
+    order.cancelled_by = Set(Some(user.id));
+    order.cancelled_at = Set(Some(Utc::now()));
+    order.cancel_reason = Set(reason);
+    order.update(&txn).await.map_err(map_db_err)?;
+
+    let balance = wallet::refund(&txn, owner_id, refunded, order_id).await?;
+    txn.commit().await.map_err(map_db_err)?;
+
+    data.events.publish(OrderEvent::StatusChanged {
+        order_id,
+        user_id: owner_id,
+        from: Status::Paid,
+        to: Status::Cancelled,
+    });
+
+    Ok(OrderCancelled {
+        order_id,
+        refunded,
+        balance,
+    })
+}
+
+#[delete("/{id}")]
+async fn cancel_order(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+    body: Option<web::Json<CancelRequest>>,
+) -> Result<web::Json<OrderCancelled>, ServiceError> {
+    let reason = body.and_then(|body| body.into_inner().reason);
+
+    cancel_paid_order(&data, path.into_inner(), &user, false, reason)
+        .await
+        .map(web::Json)
+}
+
+//Replaces lines of an order that wasn't prepared yet. Price is recomputed against the menu,
+//the difference is charged or refunded and stock of both old and new dishes is adjusted,
+//nothing changes if any step fails
+pub async fn modify_paid_order(
+    data: &AppState,
+    order_id: i32,
+    user_id: i32,
+    body: ModifyOrderRequest,
+) -> Result<OrderModified, ServiceError> {
+    let db = &data.conn;
+
+    let txn = db.begin().await.map_err(map_db_err)?;
+    let order = dinner_orders::Entity::find_by_id(order_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(map_db_err)?;
+    let Some(order) = order.filter(|o| o.user_id == user_id) else {
+        return Err(ServiceError::NotFound("No order has given id".into()));
+    };
+
+    if Status::from_repr(order.status) != Some(Status::Paid) {
+        return Err(ServiceError::Conflict(
+            "Only orders that aren't prepared yet can be changed".into(),
+        ));
+    }
+    let cutoff = order_cutoff(&order.collection_date)?;
+    if Utc::now() >= cutoff {
+        return Err(ServiceError::BadRequest(format!(
+            "Order could only be changed before {}",
+            cutoff.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        )));
+    }
+    let Some(slot_id) = order.slot_id else {
+        return Err(ServiceError::BadRequest(
+            "Order without a pickup slot can't be changed".into(),
+        ));
+    };
+
+    let day = collection_day(&order.collection_date);
+    let group = order.is_group != 0;
+    let mut request = OrderRequest {
+        dinners: body.dinners,
+        collection_day: day,
+        slot_id,
+        note: body.note,
+    };
+    clean_notes(&mut request);
+    let contents = validate_order(&txn, &request, group).await?;
+    let price = contents.price(&request);
+    let difference = to_grosze(price) - to_grosze(order.price);
+
+    let old_lines = user_dinner_orders::Entity::find()
+        .filter(user_dinner_orders::Column::OrderId.eq(order.id))
+        .all(&txn)
+        .await
+        .map_err(map_db_err)?;
+    reject_collected(&old_lines, "changed")?;
+    let old_dinner_ids = old_lines.iter().map(|l| l.dinner_id).collect::<Vec<_>>();
+    let new_dinner_ids = request
+        .dinners
+        .iter()
+        .map(|l| l.dinner_id)
+        .collect::<Vec<_>>();
+    //old portions go back first so keeping a dish that's down to its last portion still works
+    release_stock(&txn, day, &old_dinner_ids).await?;
+    reserve_stock(&txn, day, &new_dinner_ids).await?;
+
+    extras_order::Entity::delete_many()
+        .filter(extras_order::Column::UserDinnerId.is_in(old_lines.iter().map(|l| l.id)))
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+    user_dinner_orders::Entity::delete_many()
+        .filter(user_dinner_orders::Column::OrderId.eq(order.id))
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+
+    let note = request.note.clone();
+    let lines = request
+        .dinners
+        .iter()
+        .map(DinnerResponse::from)
+        .collect::<Vec<_>>();
+    let attendees = insert_lines(
+        &txn,
+        order.id,
+        request.dinners,
+        &order.collection_date,
+        group,
+    )
+    .await?;
+
+    let mut order: dinner_orders::ActiveModel = order.into();
+    order.price = Set(price);
+    order.note = Set(note.clone());
+    order.update(&txn).await.map_err(map_db_err)?;
+
+    //same as ordering, the difference moves together with the new contents
+    let balance = match difference {
+        0 => wallet::balance(&txn, user_id).await?,
+        d if d > 0 => wallet::pay(&txn, user_id, d, order_id).await?,
+        d => wallet::refund(&txn, user_id, -d, order_id).await?,
+    };
+    txn.commit().await.map_err(map_db_err)?;
+
+    data.events.publish(OrderEvent::OrderModified {
+        order_id,
+        user_id,
+        note,
+        dinners: lines.clone(),
+    });
+
+    Ok(OrderModified {
+        order_id,
+        total: to_grosze(price),
+        difference,
+        balance,
+        dinners: lines,
+        attendees,
+    })
+}
+
+#[put("/{id}")]
+async fn modify_order(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+    body: web::Json<ModifyOrderRequest>,
+) -> Result<web::Json<OrderModified>, ServiceError> {
+    modify_paid_order(&data, path.into_inner(), user.id, body.into_inner())
+        .await
+        .map(web::Json)
+}
+
+fn status_change(entry: order_history::Model) -> Result<StatusChange, ServiceError> {
+    Ok(StatusChange {
+        from: entry.from_status.and_then(Status::from_repr),
+        to: Status::from_repr(entry.to_status).ok_or(ServiceError::InternalError)?,
+        changed_by: entry.changed_by,
+        changed_at: entry.changed_at,
+        note: entry.note,
+    })
 }
 
 async fn get_user_orders(
@@ -137,68 +518,189 @@ async fn get_user_orders(
     db: &DatabaseConnection,
     status: &[Status],
 ) -> Result<web::Json<UserOrders>, ServiceError> {
-    let db_err = |err| convert_err_to_500(err, Some("Database error getting user orders"));
     let orders = dinner_orders::Entity::find()
         .filter(dinner_orders::Column::UserId.eq(user_id))
         .filter(dinner_orders::Column::Status.is_in(status.to_vec()))
         .all(db)
         .await
-        .map_err(db_err)?;
+        .map_err(map_db_err)?;
 
-    let user_dinner_orders = orders
-        .load_many(user_dinner_orders::Entity, db)
-        .await
-        .map_err(db_err)?;
+    user_orders_response(db, orders).await.map(web::Json)
+}
 
-    let mut dinners_out = HashSet::new();
-    let mut extras_out = HashSet::new();
-    let mut output: Vec<OrderResponse> = Vec::new();
+async fn user_orders_response(
+    db: &DatabaseConnection,
+    orders: Vec<dinner_orders::Model>,
+) -> Result<UserOrders, ServiceError> {
+    let loaded = load_orders(db, orders.iter()).await?;
+    let response = orders
+        .iter()
+        .map(|order| loaded.response(order, true))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    for (order, user_dinner) in orders.into_iter().zip(user_dinner_orders.into_iter()) {
-        let dinner = user_dinner.load_one(dinner::Entity, db).await?;
+    Ok(UserOrders {
+        response,
+        dinners: loaded.dinners.into_values().collect(),
+        extras: loaded.extras.into_values().collect(),
+    })
+}
 
-        let extras = user_dinner
-            .load_many_to_many(extras::Entity, extras_order::Entity, db)
-            .await
-            .map_err(db_err)?;
+pub fn line_response(line: user_dinner_orders::Model, extras_ids: Vec<i32>) -> DinnerResponse {
+    DinnerResponse {
+        dinner_id: line.dinner_id,
+        extras_ids,
+        note: line.note,
+        dietary_flags: DietaryFlag::from_bits(line.dietary_flags),
+        attendee_user_id: line.attendee_user_id,
+        attendee_name: line.attendee_name,
+        //only lines of group orders are collected one by one
+        collected: line
+            .pickup_code
+            .as_ref()
+            .map(|_| line.collected_at.is_some()),
+        pickup_code: line.pickup_code,
+    }
+}
 
-        let mut dinners_with_extras = dinner
-            .into_iter()
-            .zip(extras.into_iter())
-            .map(|(dinner, extras)| {
-                let dinner = dinner.unwrap();
-                let dinner_id = dinner.id;
-                dinners_out.insert(dinner);
-
-                let mut extras = extras
-                    .into_iter()
-                    .map(|e| {
-                        let id = e.id;
-                        extras_out.insert(e);
-                        id
-                    })
-                    .collect::<Vec<_>>();
-
-                DinnerResponse {
-                    dinner_id,
-                    extras_ids: mem::take(&mut extras),
-                }
-            })
-            .collect::<Vec<_>>();
+//Everything needed to describe a batch of orders, fetched with one query per table
+//no matter how many orders or lines there are
+struct LoadedOrders {
+    lines: HashMap<i32, Vec<DinnerResponse>>,
+    histories: HashMap<i32, Vec<order_history::Model>>,
+    dinners: HashMap<i32, dinner::Model>,
+    extras: HashMap<i32, extras::Model>,
+}
 
-        output.push(OrderResponse {
+impl LoadedOrders {
+    //QR payload is only meant for the order's owner
+    fn response(
+        &self,
+        order: &dinner_orders::Model,
+        with_qr: bool,
+    ) -> Result<OrderResponse, ServiceError> {
+        let status = Status::from_repr(order.status).ok_or(ServiceError::InternalError)?;
+        //QR is only useful until the order is collected
+        let qr_payload = match (&order.pickup_code, status.forward()) {
+            (Some(code), Some(_)) if with_qr => {
+                Some(qr_payload(order.id, code, &order.collection_date)?)
+            }
+            _ => None,
+        };
+
+        let dinners = self.lines.get(&order.id).cloned().unwrap_or_default();
+        let has_notes = order.note.is_some()
+            || dinners
+                .iter()
+                .any(|line| line.note.is_some() || !line.dietary_flags.is_empty());
+
+        Ok(OrderResponse {
             order_id: order.id,
             collection_date: order.collection_date,
-            status: Status::from_repr(order.status).unwrap(),
-            dinners: mem::take(&mut dinners_with_extras),
-        });
+            slot_id: order.slot_id,
+            status,
+            group: order.is_group != 0,
+            note: order.note.clone(),
+            has_notes,
+            dinners,
+            history: self
+                .histories
+                .get(&order.id)
+                .into_iter()
+                .flatten()
+                .cloned()
+                .map(status_change)
+                .collect::<Result<_, _>>()?,
+            pickup_code: order.pickup_code.clone(),
+            qr_payload,
+        })
+    }
+}
+
+async fn load_orders<'a>(
+    db: &DatabaseConnection,
+    orders: impl Iterator<Item = &'a dinner_orders::Model>,
+) -> Result<LoadedOrders, ServiceError> {
+    let order_ids = orders.map(|order| order.id).collect::<Vec<_>>();
+    let mut loaded = LoadedOrders {
+        lines: HashMap::new(),
+        histories: HashMap::new(),
+        dinners: HashMap::new(),
+        extras: HashMap::new(),
+    };
+    if order_ids.is_empty() {
+        return Ok(loaded);
+    }
+
+    let lines = user_dinner_orders::Entity::find()
+        .filter(user_dinner_orders::Column::OrderId.is_in(order_ids.clone()))
+        .order_by_asc(user_dinner_orders::Column::Id)
+        .all(db)
+        .await
+        .map_err(map_db_err)?;
+
+    let mut line_extras: HashMap<i32, Vec<i32>> = HashMap::new();
+    if !lines.is_empty() {
+        let extras_orders = extras_order::Entity::find()
+            .filter(extras_order::Column::UserDinnerId.is_in(lines.iter().map(|l| l.id)))
+            .order_by_asc(extras_order::Column::Id)
+            .all(db)
+            .await
+            .map_err(map_db_err)?;
+        for extra in extras_orders {
+            line_extras
+                .entry(extra.user_dinner_id)
+                .or_default()
+                .push(extra.extras_id);
+        }
     }
 
-    Ok(web::Json(UserOrders {
-        response: mem::take(&mut output),
-        dinners: mem::take(&mut dinners_out),
-        extras: mem::take(&mut extras_out),
-    }))
+    let dinner_ids: HashSet<_> = lines.iter().map(|l| l.dinner_id).collect();
+    let extras_ids: HashSet<_> = line_extras.values().flatten().copied().collect();
+    if !dinner_ids.is_empty() {
+        loaded.dinners = dinner::Entity::find()
+            .filter(dinner::Column::Id.is_in(dinner_ids))
+            .all(db)
+            .await
+            .map_err(map_db_err)?
+            .into_iter()
+            .map(|dinner| (dinner.id, dinner))
+            .collect();
+    }
+    if !extras_ids.is_empty() {
+        loaded.extras = extras::Entity::find()
+            .filter(extras::Column::Id.is_in(extras_ids))
+            .all(db)
+            .await
+            .map_err(map_db_err)?
+            .into_iter()
+            .map(|extra| (extra.id, extra))
+            .collect();
+    }
+
+    for line in lines {
+        let extras_ids = line_extras.remove(&line.id).unwrap_or_default();
+        loaded
+            .lines
+            .entry(line.order_id)
+            .or_default()
+            .push(line_response(line, extras_ids));
+    }
+
+    let histories = order_history::Entity::find()
+        .filter(order_history::Column::OrderId.is_in(order_ids))
+        .order_by_asc(order_history::Column::Id)
+        .all(db)
+        .await
+        .map_err(map_db_err)?;
+    for entry in histories {
+        loaded
+            .histories
+            .entry(entry.order_id)
+            .or_default()
+            .push(entry);
+    }
+
+    Ok(loaded)
 }
 
 #[get("/completed")]
@@ -220,144 +722,321 @@ async fn get_pending_user_orders(
     let db = &data.conn;
     let user_id = user.id;
 
-    get_user_orders(
-        user_id,
-        db,
-        &[Status::Paid, Status::Prepared, Status::Ready],
-    )
-    .await
+    get_user_orders(user_id, db, &PENDING_STATUSES).await
+}
+
+const ALL_STATUSES: [Status; 6] = [
+    Status::Paid,
+    Status::Prepared,
+    Status::Ready,
+    Status::Collected,
+    Status::Cancelled,
+    Status::NoShow,
+];
+
+pub const PENDING_STATUSES: [Status; 3] = [Status::Paid, Status::Prepared, Status::Ready];
+
+//Applies list filters and sorting, `allowed` limits statuses whatever the filter asks for
+pub fn filtered_orders(filter: &OrderFilter, allowed: &[Status]) -> Select<dinner_orders::Entity> {
+    let statuses = allowed
+        .iter()
+        .copied()
+        .filter(|status| filter.status.as_ref().is_none_or(|s| s.contains(status)))
+        .collect::<Vec<_>>();
+    let mut select =
+        dinner_orders::Entity::find().filter(dinner_orders::Column::Status.is_in(statuses));
+
+    if let Some(from) = filter.from {
+        select = select.filter(dinner_orders::Column::CollectionDate.gte(day_bounds(from).0));
+    }
+    if let Some(to) = filter.to {
+        select = select.filter(dinner_orders::Column::CollectionDate.lt(day_bounds(to).1));
+    }
+    if filter.has_notes {
+        select = select.filter(
+            Condition::any()
+                .add(dinner_orders::Column::Note.is_not_null())
+                .add(
+                    dinner_orders::Column::Id.in_subquery(
+                        Query::select()
+                            .column(user_dinner_orders::Column::OrderId)
+                            .from(user_dinner_orders::Entity)
+                            .cond_where(
+                                Condition::any()
+                                    .add(user_dinner_orders::Column::Note.is_not_null())
+                                    .add(user_dinner_orders::Column::DietaryFlags.ne(0)),
+                            )
+                            .to_owned(),
+                    ),
+                ),
+        );
+    }
+    if let Some(dinner_id) = filter.dinner_id {
+        select = select.filter(
+            dinner_orders::Column::Id.in_subquery(
+                Query::select()
+                    .column(user_dinner_orders::Column::OrderId)
+                    .from(user_dinner_orders::Entity)
+                    .and_where(user_dinner_orders::Column::DinnerId.eq(dinner_id))
+                    .to_owned(),
+            ),
+        );
+    }
+
+    let order = match filter.direction {
+        SortDirection::Asc => Order::Asc,
+        SortDirection::Desc => Order::Desc,
+    };
+    let column = match filter.sort {
+        OrderSort::CollectionDate => dinner_orders::Column::CollectionDate,
+        OrderSort::OrderId => dinner_orders::Column::Id,
+        OrderSort::Price => dinner_orders::Column::Price,
+    };
+
+    //id as tie breaker keeps pages stable
+    select
+        .order_by(column, order.clone())
+        .order_by(dinner_orders::Column::Id, order)
 }
 
 #[get("/")]
 async fn get_all_user_orders(
     user: AuthUser,
     data: web::Data<AppState>,
-) -> Result<web::Json<UserOrders>, ServiceError> {
+    pagination: Pagination,
+    filter: web::Query<OrderFilter>,
+) -> Result<web::Json<Page<UserOrders>>, ServiceError> {
     let db = &data.conn;
-    let user_id = user.id;
 
-    get_user_orders(
-        user_id,
-        db,
-        &[
-            Status::Paid,
-            Status::Prepared,
-            Status::Ready,
-            Status::Collected,
-        ],
-    )
-    .await
+    let select =
+        filtered_orders(&filter, &ALL_STATUSES).filter(dinner_orders::Column::UserId.eq(user.id));
+    let (orders, total) = pagination.fetch(db, select).await?;
+    let orders = user_orders_response(db, orders).await?;
+
+    Ok(web::Json(pagination.page_of(orders, total)))
 }
 
 #[get("/")]
 async fn get_all_orders(
     user: AuthUser,
     data: web::Data<AppState>,
-) -> Result<web::Json<AllUsersOrders>, ServiceError> {
+    pagination: Pagination,
+    filter: web::Query<OrderFilter>,
+) -> Result<web::Json<Page<AllUsersOrders>>, ServiceError> {
     if !user.is_admin {
         return Err(ServiceError::Unauthorized(
             "Only admin can access that data".to_string(),
         ));
     }
-    let db = &data.conn;
 
-    let users_with_orders = user::Entity::find()
-        .find_with_related(dinner_orders::Entity)
-        .all(db)
-        .await
-        .map_err(map_db_err)?;
-
-    get_all_orders_from_users(db, users_with_orders).await
+    admin_order_page(&data.conn, pagination, &filter, &ALL_STATUSES).await
 }
 
 #[get("/pending")]
 async fn get_all_pending_orders(
     user: AuthUser,
     data: web::Data<AppState>,
-) -> Result<web::Json<AllUsersOrders>, ServiceError> {
+    pagination: Pagination,
+    filter: web::Query<OrderFilter>,
+) -> Result<web::Json<Page<AllUsersOrders>>, ServiceError> {
     if !user.is_admin {
         return Err(ServiceError::Unauthorized(
             "Only admin can access that data".to_string(),
         ));
     }
-    let db = &data.conn;
 
-    let users_with_orders = user::Entity::find()
-        .find_with_related(dinner_orders::Entity)
-        .filter(dinner_orders::Column::Status.ne(Status::Collected))
+    admin_order_page(&data.conn, pagination, &filter, &PENDING_STATUSES).await
+}
+
+async fn admin_order_page(
+    db: &DatabaseConnection,
+    pagination: Pagination,
+    filter: &OrderFilter,
+    allowed: &[Status],
+) -> Result<web::Json<Page<AllUsersOrders>>, ServiceError> {
+    let mut select = filtered_orders(filter, allowed);
+    if let Some(user_id) = filter.user_id {
+        select = select.filter(dinner_orders::Column::UserId.eq(user_id));
+    }
+    let (orders, total) = pagination.fetch(db, select).await?;
+
+    let users: HashMap<_, _> = user::Entity::find()
+        .filter(user::Column::Id.is_in(orders.iter().map(|o| o.user_id)))
         .all(db)
         .await
-        .map_err(map_db_err)?;
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect();
 
-    get_all_orders_from_users(db, users_with_orders).await
+    //users are listed in order of their first order on the page
+    let mut users_with_orders: Vec<(user::Model, Vec<dinner_orders::Model>)> = Vec::new();
+    for order in orders {
+        match users_with_orders
+            .iter_mut()
+            .find(|(user, _)| user.id == order.user_id)
+        {
+            Some((_, orders)) => orders.push(order),
+            None => {
+                let user = users
+                    .get(&order.user_id)
+                    .cloned()
+                    .ok_or(ServiceError::InternalError)?;
+                users_with_orders.push((user, vec![order]));
+            }
+        }
+    }
+
+    let orders = get_all_orders_from_users(db, users_with_orders).await?;
+    Ok(web::Json(pagination.page_of(orders, total)))
 }
 
 async fn get_all_orders_from_users(
     db: &DatabaseConnection,
     users_with_orders: Vec<(user::Model, Vec<dinner_orders::Model>)>,
-) -> Result<web::Json<AllUsersOrders>, ServiceError> {
-    let mut dinners_out = HashSet::new();
-    let mut extras_out = HashSet::new();
-    let mut output: Vec<UserWithOrders> = Vec::with_capacity(users_with_orders.len());
-
-    for (user, orders) in users_with_orders.iter() {
-        output.push(UserWithOrders {
-            username: user.username.clone(),
-            user_id: user.id,
-            orders: Vec::new(),
-        });
-
-        let user_dinner_orders = orders
-            .load_many(user_dinner_orders::Entity, db)
-            .await
-            .map_err(map_db_err)?;
+) -> Result<AllUsersOrders, ServiceError> {
+    let loaded = load_orders(
+        db,
+        users_with_orders
+            .iter()
+            .flat_map(|(_, orders)| orders.iter()),
+    )
+    .await?;
 
-        for (user_dinner, order) in user_dinner_orders.iter().zip(orders.iter()) {
-            let dinner = user_dinner
-                .load_one(dinner::Entity, db)
-                .await
-                .map_err(map_db_err)?;
-            let extras = user_dinner
-                .load_many_to_many(extras::Entity, extras_order::Entity, db)
-                .await
-                .map_err(map_db_err)?;
+    let response = users_with_orders
+        .iter()
+        .map(|(user, orders)| {
+            Ok(UserWithOrders {
+                username: user.username.clone(),
+                user_id: user.id,
+                orders: orders
+                    .iter()
+                    .map(|order| loaded.response(order, false))
+                    .collect::<Result<Vec<_>, ServiceError>>()?,
+            })
+        })
+        .collect::<Result<Vec<_>, ServiceError>>()?;
 
-            let mut dinners_with_extras = dinner
-                .into_iter()
-                .zip(extras.into_iter())
-                .map(|(dinner, extras)| {
-                    let dinner = dinner.unwrap();
-                    let dinner_id = dinner.id;
-                    dinners_out.insert(dinner);
-
-                    let mut extras = extras
-                        .into_iter()
-                        .map(|e| {
-                            let id = e.id;
-                            extras_out.insert(e);
-                            id
-                        })
-                        .collect::<Vec<_>>();
-
-                    DinnerResponse {
-                        dinner_id,
-                        extras_ids: mem::take(&mut extras),
-                    }
-                })
-                .collect::<Vec<_>>();
+    Ok(AllUsersOrders {
+        response,
+        dinners: loaded.dinners.into_values().collect(),
+        extras: loaded.extras.into_values().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use entity::sea_orm_active_enums::{ExtrasType, Type};
+    use sea_orm::{DbBackend, MockDatabase};
 
-            output.last_mut().unwrap().orders.push(OrderResponse {
+    use super::*;
+
+    const DINNERS: i32 = 4;
+    const EXTRAS: i32 = 3;
+
+    //`count` orders of two lines with an extra each, loaded as a listing would
+    async fn load(count: i32) -> (LoadedOrders, Vec<dinner_orders::Model>, usize) {
+        let orders = (1..=count)
+            .map(|id| dinner_orders::Model {
+                id,
+                user_id: id % 7,
+                collection_date: Utc::now(),
+                status: Status::Paid.into_value(),
+                price: Decimal::new(1800, 2),
+                cancelled_by: None,
+                cancelled_at: None,
+                cancel_reason: None,
+                pickup_code: Some(format!("{:06}", id)),
+                slot_id: None,
+                note: None,
+                is_group: 0,
+            })
+            .collect::<Vec<_>>();
+        let lines = orders
+            .iter()
+            .flat_map(|order| {
+                (0..2).map(move |n| user_dinner_orders::Model {
+                    id: order.id * 2 + n,
+                    order_id: order.id,
+                    dinner_id: (order.id + n) % DINNERS,
+                    note: None,
+                    dietary_flags: 0,
+                    attendee_user_id: None,
+                    attendee_name: None,
+                    pickup_code: None,
+                    collected_at: None,
+                })
+            })
+            .collect::<Vec<_>>();
+        let extras_orders = lines
+            .iter()
+            .map(|line| extras_order::Model {
+                id: line.id,
+                user_dinner_id: line.id,
+                extras_id: line.id % EXTRAS,
+            })
+            .collect::<Vec<_>>();
+        let dinners = (0..DINNERS)
+            .map(|id| dinner::Model {
+                id,
+                name: format!("dinner {}", id),
+                price: Decimal::new(1500, 2),
+                image: String::new(),
+                week_day: 0,
+                max_supply: 100,
+                r#type: Type::Main,
+            })
+            .collect::<Vec<_>>();
+        let extras = (0..EXTRAS)
+            .map(|id| extras::Model {
+                id,
+                name: format!("extra {}", id),
+                price: Decimal::new(300, 2),
+                image: String::new(),
+                r#type: ExtrasType::Salad,
+            })
+            .collect::<Vec<_>>();
+        let histories = orders
+            .iter()
+            .map(|order| order_history::Model {
+                id: order.id,
                 order_id: order.id,
-                collection_date: order.collection_date,
-                status: Status::from_repr(order.status).unwrap(),
-                dinners: mem::take(&mut dinners_with_extras),
-            });
-        }
+                from_status: None,
+                to_status: Status::Paid.into_value(),
+                changed_by: Some(order.user_id),
+                changed_at: order.collection_date,
+                note: None,
+            })
+            .collect::<Vec<_>>();
+
+        let db = MockDatabase::new(DbBackend::MySql)
+            .append_query_results([lines])
+            .append_query_results([extras_orders])
+            .append_query_results([dinners])
+            .append_query_results([extras])
+            .append_query_results([histories])
+            .into_connection();
+        let loaded = load_orders(&db, orders.iter()).await.unwrap();
+        (loaded, orders, db.into_transaction_log().len())
     }
 
-    Ok(web::Json(AllUsersOrders {
-        response: mem::take(&mut output),
-        dinners: mem::take(&mut dinners_out),
-        extras: mem::take(&mut extras_out),
-    }))
+    #[actix_rt::test]
+    async fn loading_orders_takes_the_same_queries_for_any_amount() {
+        std::env::set_var("JWT_SECRET", "test secret");
+        let (_, _, few) = load(1).await;
+        let (loaded, orders, many) = load(500).await;
+
+        //lines, their extras, dinners, extras and histories
+        assert_eq!(few, 5);
+        assert_eq!(many, few);
+        for order in orders.iter() {
+            let response = loaded.response(order, true).unwrap();
+            assert_eq!(response.dinners.len(), 2);
+            assert!(response
+                .dinners
+                .iter()
+                .all(|line| line.extras_ids.len() == 1));
+            assert_eq!(response.history.len(), 1);
+        }
+    }
 }
@@ -1,47 +1,62 @@
-use actix_web::{delete, get, post, web, HttpRequest};
-use entity::{prelude::User, user};
-use sea_orm::{ActiveModelTrait, EntityTrait, Set};
-use std::{borrow::Borrow, collections::HashMap, mem};
-use stripe::{
-    self, CreateCustomer, CreatePaymentIntent, Customer, EventObject, EventType, PaymentIntent,
-    UpdateCustomer, Webhook,
+use actix_web::{delete, get, http::header, post, web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use entity::{payment_customer, prelude::User, user};
+use log::error;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, TransactionTrait,
 };
+use std::borrow::Borrow;
 
 use crate::{
-    appstate::AppState, convert_err_to_500, errors::ServiceError, get_header_val, get_user,
-    jwt_auth::AuthUser, map_db_err,
+    appstate::AppState,
+    customer_id,
+    errors::ServiceError,
+    get_header_val,
+    idempotency::Idempotency,
+    jwt_auth::AuthUser,
+    map_db_err,
+    pagination::{Page, Pagination},
+    payments::{CustomerDetails, NewCustomer},
+    wallet::{self, history, refund_to_card, transaction_response, transactions_csv},
+    webhooks,
 };
 
-use super::structs::{AddReturn, StripeUser};
+use super::structs::{
+    AddReturn, CardRefundRequest, StripeUser, TransactionFilter, WalletTransactions,
+};
 
+//retried with the same Idempotency-Key it returns the first intent instead of creating another
 #[post("/add-balance/{amount:[0-9]+}")]
 async fn add_balance(
     data: web::Data<AppState>,
     amount: web::Path<i64>,
     user: AuthUser,
-) -> Result<web::Json<AddReturn>, ServiceError> {
-    let client = &data.stripe_client.0;
-    let customer = get_user(&data.conn, user.id, client).await?;
-    let customer_id = customer.id.to_string();
+    idempotency: Idempotency,
+) -> Result<HttpResponse, ServiceError> {
+    idempotency
+        .respond(
+            &data.conn,
+            user.id,
+            create_intent(&data, user.id, amount.into_inner()),
+        )
+        .await
+}
 
-    let intent = {
-        let mut intent = CreatePaymentIntent::new(amount.into_inner(), stripe::Currency::PLN);
-        intent.payment_method_types = Some(vec!["card".into(), "p24".into()]);
-        intent.customer = Some(customer.id);
-        intent.expand = &["customer"];
+async fn create_intent(
+    data: &AppState,
+    user_id: i32,
+    amount: i64,
+) -> Result<AddReturn, ServiceError> {
+    let customer_id = customer_id(&data.conn, user_id).await?;
+    let intent_secret = data.payments.create_intent(&customer_id, amount).await?;
 
-        PaymentIntent::create(client, intent)
-            .await
-            .map_err(|e| convert_err_to_500(e, Some("Stripe Error")))?
-    };
-
-    Ok(web::Json(AddReturn {
+    Ok(AddReturn {
         customer_id,
-        intent_secret: intent.client_secret.unwrap(),
-    }))
+        intent_secret,
+    })
 }
 
-//Webhook for stripe to use
+//Webhook for the payment provider to use
 #[post("/received")]
 async fn received_payment(
     req: HttpRequest,
@@ -52,59 +67,145 @@ async fn received_payment(
 
     let stripe_sig = get_header_val(&req, "stripe-signature").unwrap_or_default();
 
-    let web_hook_secret: String =
-        dotenvy::var("WEBHOOK_SECRET").expect("No WEBHOOK_SECRET provided in .env");
-    let Ok(event) = Webhook::construct_event(payload_str, stripe_sig, &web_hook_secret) else {return Err(ServiceError::InternalError)};
-
-    //for dev reasons @ release switch to payment intent success
-    if event.event_type == EventType::PaymentIntentSucceeded {
-        let EventObject::PaymentIntent(intent_data) = event.data.object else {return Err(ServiceError::InternalError)};
-
-        let client = &data.stripe_client.0;
-        let customer = &intent_data.customer.unwrap();
-        let customer_id = &customer.id();
-        let customer_balance = Customer::retrieve(client, customer_id, &[])
-            .await
-            .map_err(|e| convert_err_to_500(e, Some("Stripe error")))?
-            .balance
-            .unwrap();
-
-        Customer::update(
-            client,
-            customer_id,
-            UpdateCustomer {
-                balance: Some(intent_data.amount + customer_balance),
-                ..Default::default()
-            },
-        )
-        .await
-        .map_err(|e| convert_err_to_500(e, Some("Stripe error")))?;
-    } else {
-        return Err(ServiceError::InternalError);
-    }
+    //retried deliveries and event types the app doesn't act on are acknowledged too
+    webhooks::receive(&data.conn, data.payments.as_ref(), payload_str, stripe_sig).await?;
 
     Ok("tak".into())
 }
 
 #[get("/balance")]
 async fn get_balance(user: AuthUser, data: web::Data<AppState>) -> Result<String, ServiceError> {
-    let user = get_user(&data.conn, user.id, &data.stripe_client.0).await?;
-    Ok(serde_json::json!({"balance": user.balance.unwrap()}).to_string())
+    let balance = wallet::balance(&data.conn, user.id).await?;
+    Ok(serde_json::json!({ "balance": balance }).to_string())
+}
+
+//every top-up, order payment, refund and correction of the wallet, newest first
+#[get("/transactions")]
+async fn get_transactions(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    pagination: Pagination,
+    filter: web::Query<TransactionFilter>,
+) -> Result<web::Json<Page<WalletTransactions>>, ServiceError> {
+    let conn = &data.conn;
+    let Some(select) = history(conn, user.id, &filter).await? else {
+        let transactions = Vec::new();
+        return Ok(web::Json(
+            pagination.page_of(WalletTransactions { transactions }, 0),
+        ));
+    };
+
+    let (rows, total) = pagination.fetch(conn, select).await?;
+    let transactions = rows
+        .into_iter()
+        .map(|(entry, transaction)| transaction_response(entry, transaction))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(web::Json(
+        pagination.page_of(WalletTransactions { transactions }, total),
+    ))
+}
+
+//the same history as a csv file, filtered but not paginated
+#[get("/transactions/csv")]
+async fn export_transactions(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    filter: web::Query<TransactionFilter>,
+) -> Result<HttpResponse, ServiceError> {
+    let conn = &data.conn;
+    let transactions = match history(conn, user.id, &filter).await? {
+        Some(select) => select
+            .all(conn)
+            .await
+            .map_err(map_db_err)?
+            .into_iter()
+            .map(|(entry, transaction)| transaction_response(entry, transaction))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"transactions.csv\"",
+        ))
+        .body(transactions_csv(&transactions)))
 }
 
 #[get("/details")]
 async fn customer_details(
     user: AuthUser,
     data: web::Data<AppState>,
-) -> Result<web::Json<Customer>, ServiceError> {
-    let user = get_user(&data.conn, user.id, &data.stripe_client.0).await?;
-    Ok(web::Json(user))
+) -> Result<web::Json<CustomerDetails>, ServiceError> {
+    let customer_id = customer_id(&data.conn, user.id).await?;
+    let customer = data.payments.customer(&customer_id).await?;
+    Ok(web::Json(customer))
 }
 
+//Sends unspent balance back to the cards it was topped up with.
+//Safe to retry with the same Idempotency-Key, the money is refunded only once
+#[post("/refund")]
+async fn refund_balance(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    idempotency: Idempotency,
+    body: web::Json<CardRefundRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let body = body.into_inner();
+    idempotency
+        .with_body(&body)?
+        .respond(
+            &data.conn,
+            user.id,
+            refund_to_card(
+                &data.conn,
+                data.payments.as_ref(),
+                user.id,
+                body.amount,
+                None,
+            ),
+        )
+        .await
+}
+
+//money left in the wallet has to be refunded first so it isn't lost with the customer
 #[delete("/wallet")]
 async fn delete_wallet(user: AuthUser, data: web::Data<AppState>) -> Result<String, ServiceError> {
-    let user = get_user(&data.conn, user.id, &data.stripe_client.0).await?;
-    Customer::delete(&data.stripe_client.0, &user.id);
+    let conn = &data.conn;
+    let customer_id = customer_id(conn, user.id).await?;
+
+    //account stays locked until the customer is marked deleted, no payment can slip in after
+    //the check
+    let txn = conn.begin().await.map_err(map_db_err)?;
+    if wallet::locked_balance(&txn, user.id).await? != 0 {
+        return Err(ServiceError::BadRequest(
+            "Wallet balance has to be 0 before deleting it, request a refund first".into(),
+        ));
+    }
+
+    //lets the user initialize the wallet again, the old customer is kept for its webhooks
+    let user_upd = user::ActiveModel {
+        id: Set(user.id),
+        stripe_id: Set(None),
+        ..Default::default()
+    };
+    user_upd.update(&txn).await.map_err(map_db_err)?;
+    payment_customer::Entity::update_many()
+        .col_expr(payment_customer::Column::DeletedAt, Expr::value(Utc::now()))
+        .filter(payment_customer::Column::CustomerId.eq(customer_id.as_str()))
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+    txn.commit().await.map_err(map_db_err)?;
+
+    //the wallet is gone already, a customer the provider didn't delete is retried by a job
+    if let Err(err) =
+        wallet::delete_provider_customer(conn, data.payments.as_ref(), &customer_id).await
+    {
+        error!("Deleting customer {} failed: {}", customer_id, err);
+    }
+
     Ok("Delete wallet".into())
 }
 
@@ -112,7 +213,7 @@ async fn delete_wallet(user: AuthUser, data: web::Data<AppState>) -> Result<Stri
 async fn init_wallet(
     user: AuthUser,
     data: web::Data<AppState>,
-    mut stripe_data: web::Json<StripeUser>,
+    stripe_data: web::Json<StripeUser>,
 ) -> Result<String, ServiceError> {
     if !user.is_verified {
         return Err(ServiceError::BadRequest(
@@ -120,7 +221,6 @@ async fn init_wallet(
         ));
     }
 
-    let client = &data.stripe_client.0;
     let conn = &data.conn;
 
     let user = User::find_by_id(user.id)
@@ -135,33 +235,30 @@ async fn init_wallet(
         ));
     }
 
-    let addr = {
-        let addr = &mut stripe_data.address;
-        stripe::Address {
-            city: Some(mem::take(&mut addr.city)),
-            country: Some(mem::take(&mut addr.country)),
-            postal_code: Some(mem::take(&mut addr.postal_code)),
-            state: Some(mem::take(&mut addr.state)),
-            ..Default::default()
-        }
-    };
-    let customer = Customer::create(
-        client,
-        CreateCustomer {
-            email: Some(&user.email),
-            name: Some(&stripe_data.name),
-            phone: Some(&stripe_data.phone),
-            address: Some(addr),
-            metadata: Some(HashMap::from([("async-stripe".into(), "true".into())])),
-            ..Default::default()
-        },
-    )
-    .await
-    .map_err(|e| convert_err_to_500(e, Some("Stripe error")))?;
+    let customer_id = data
+        .payments
+        .create_customer(NewCustomer {
+            email: &user.email,
+            name: &stripe_data.name,
+            phone: &stripe_data.phone,
+            address: &stripe_data.address,
+        })
+        .await?;
 
+    let txn = conn.begin().await.map_err(map_db_err)?;
+    payment_customer::ActiveModel {
+        customer_id: Set(customer_id.clone()),
+        user_id: Set(user.id),
+        created_at: Set(Utc::now()),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await
+    .map_err(map_db_err)?;
     let mut user_upd: user::ActiveModel = user.into();
-    user_upd.stripe_id = Set(Some(customer.id.to_string()));
-    user_upd.update(conn).await.map_err(map_db_err)?;
+    user_upd.stripe_id = Set(Some(customer_id));
+    user_upd.update(&txn).await.map_err(map_db_err)?;
+    txn.commit().await.map_err(map_db_err)?;
 
     Ok("Success".into())
 }
@@ -1,11 +1,13 @@
 use std::collections::HashSet;
 
-use chrono::serde::ts_seconds;
-use chrono::{DateTime, Utc};
-use entity::model_enums::Status;
+use chrono::serde::{ts_seconds, ts_seconds_option};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use entity::model_enums::{DietaryFlag, Status, TransactionKind, WebhookStatus};
 use entity::{dinner, extras};
 use serde::{Deserialize, Serialize};
 
+use crate::pagination::comma_separated;
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UserRegister {
@@ -55,17 +57,26 @@ pub struct MenuResult3D {
 
 #[derive(Serialize)]
 pub struct MenuOneDay {
-    pub dinners: Vec<dinner::Model>,
+    pub dinners: Vec<DinnerWithStock>,
     pub extras: Vec<extras::Model>,
 }
 
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DinnerWithExtras {
-    pub dinners: Vec<dinner::Model>,
+    pub dinners: Vec<DinnerWithStock>,
     pub extras_ids: Vec<i32>,
 }
 
+//dinner model with portions still available on its next collection day
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DinnerWithStock {
+    #[serde(flatten)]
+    pub dinner: dinner::Model,
+    pub remaining: i32,
+}
+
 #[derive(Serialize, Deserialize, Default)]
 pub struct UpdateMenu {
     pub id: i32,
@@ -81,6 +92,16 @@ pub struct UpdateMenu {
 pub struct Dinner {
     pub dinner_id: i32,
     pub extras_ids: Vec<i32>,
+    //free text for the kitchen, e.g. "no sauce"
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub dietary_flags: Vec<DietaryFlag>,
+    //who the dish is for, only allowed in group orders
+    #[serde(default)]
+    pub attendee_user_id: Option<i32>,
+    #[serde(default)]
+    pub attendee_name: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -94,21 +115,233 @@ pub struct LastUpdateResponse{
 #[serde(rename_all = "camelCase")]
 pub struct OrderStatusRequest {
     pub new_status: Status,
+    pub note: Option<String>,
+}
+
+//orders are picked either by ids or by a filter, never both
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkStatusRequest {
+    pub new_status: Status,
+    pub note: Option<String>,
+    pub order_ids: Option<Vec<i32>>,
+    pub filter: Option<BulkOrderFilter>,
+}
+
+//matches only orders that are still open (Paid, Prepared or Ready)
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkOrderFilter {
+    pub day: NaiveDate,
+    pub slot_id: Option<i32>,
+    pub dinner_id: Option<i32>,
+    pub status: Option<Vec<Status>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkStatusResult {
+    pub order_id: i32,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<Status>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkStatusResponse {
+    pub changed: usize,
+    pub failed: usize,
+    pub results: Vec<BulkStatusResult>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderRequest {
     pub dinners: Vec<Dinner>,
+    //local date, e.g. 2023-04-24
+    pub collection_day: NaiveDate,
+    pub slot_id: i32,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderCreated {
+    pub order_id: i32,
+    //both in grosze, same as wallet balance
+    pub total: i64,
+    pub balance: i64,
+    pub pickup_code: String,
+    pub qr_payload: String,
+    //per-person codes of a group order, in the order of requested lines
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attendees: Vec<AttendeeCode>,
+}
+
+//new contents of a paid order, collection day and slot stay the same
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifyOrderRequest {
+    pub dinners: Vec<Dinner>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderModified {
+    pub order_id: i32,
+    //all in grosze, positive difference was charged and negative refunded
+    pub total: i64,
+    pub difference: i64,
+    pub balance: i64,
+    pub dinners: Vec<DinnerResponse>,
+    //new per-person codes of a group order, the old ones stop working
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attendees: Vec<AttendeeCode>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttendeeCode {
+    pub dinner_id: i32,
+    pub attendee_user_id: Option<i32>,
+    pub attendee_name: Option<String>,
+    pub pickup_code: String,
+    pub qr_payload: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FavouriteRequest {
+    pub order_id: i32,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FavouriteResponse {
+    pub favourite_id: i32,
+    pub name: String,
     #[serde(with = "ts_seconds")]
-    pub collection_date: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub dinners: Vec<DinnerResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReorderRequest {
+    pub collection_day: NaiveDate,
+    pub slot_id: i32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reordered {
+    #[serde(flatten)]
+    pub order: OrderCreated,
+    //lines of the new order, already mapped to target day's menu
+    pub dinners: Vec<DinnerResponse>,
+    pub skipped: Vec<SkippedLine>,
 }
+
+//part of the old order that couldn't be carried over,
+//extras_id is set when only the extra was dropped and the dinner itself was ordered
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedLine {
+    pub dinner_id: i32,
+    pub extras_id: Option<i32>,
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionRequest {
+    pub week_days: Vec<entity::model_enums::Weekday>,
+    pub pickup_time: NaiveTime,
+    #[serde(default = "default_true")]
+    pub with_soup: bool,
+    #[serde(default)]
+    pub extras_ids: Vec<i32>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionResponse {
+    pub subscription_id: i32,
+    pub week_days: Vec<entity::model_enums::Weekday>,
+    pub pickup_time: NaiveTime,
+    pub with_soup: bool,
+    pub active: bool,
+    pub extras_ids: Vec<i32>,
+    //days handled in the last two weeks
+    pub days: Vec<SubscriptionDayResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionDayResponse {
+    pub day: NaiveDate,
+    pub order_id: Option<i32>,
+    pub failure: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct CancelRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderCancelled {
+    pub order_id: i32,
+    //both in grosze
+    pub refunded: i64,
+    pub balance: i64,
+}
+
 //DinnerResponse
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DinnerResponse {
     pub dinner_id: i32,
     pub extras_ids: Vec<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dietary_flags: Vec<DietaryFlag>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attendee_user_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attendee_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pickup_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collected: Option<bool>,
+}
+
+impl From<&Dinner> for DinnerResponse {
+    fn from(line: &Dinner) -> Self {
+        DinnerResponse {
+            dinner_id: line.dinner_id,
+            extras_ids: line.extras_ids.clone(),
+            note: line.note.clone(),
+            dietary_flags: line.dietary_flags.clone(),
+            attendee_user_id: line.attendee_user_id,
+            attendee_name: line.attendee_name.clone(),
+            pickup_code: None,
+            collected: None,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -117,10 +350,104 @@ pub struct OrderResponse {
     pub order_id: i32,
     #[serde(with = "ts_seconds")]
     pub collection_date: DateTime<Utc>,
+    pub slot_id: Option<i32>,
     pub status: Status,
+    //placed by one user for several people
+    pub group: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    //true when the order or any of its lines carries a note or a dietary flag
+    pub has_notes: bool,
+    pub dinners: Vec<DinnerResponse>,
+    pub history: Vec<StatusChange>,
+    pub pickup_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qr_payload: Option<String>,
+}
+
+//query filters of order lists, days are local dates and both ends are inclusive
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderFilter {
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub status: Option<Vec<Status>>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub dinner_id: Option<i32>,
+    //only orders with a note or a dietary flag somewhere
+    #[serde(default)]
+    pub has_notes: bool,
+    //admin lists only
+    pub user_id: Option<i32>,
+    #[serde(default)]
+    pub sort: OrderSort,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderSort {
+    #[default]
+    CollectionDate,
+    OrderId,
+    Price,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotAvailability {
+    pub slot_id: i32,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub capacity: i32,
+    pub remaining: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotRequest {
+    pub week_day: entity::model_enums::Weekday,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub capacity: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedeemRequest {
+    //content of scanned QR code
+    pub payload: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedeemedOrder {
+    pub order_id: i32,
+    pub user_id: i32,
+    pub pickup_code: String,
     pub dinners: Vec<DinnerResponse>,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusChange {
+    pub from: Option<Status>,
+    pub to: Status,
+    pub changed_by: Option<i32>,
+    #[serde(with = "ts_seconds")]
+    pub changed_at: DateTime<Utc>,
+    pub note: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct UserWithOrders {
     pub user_id: i32,
@@ -162,3 +489,193 @@ pub struct UserOrders {
     pub dinners: HashSet<dinner::Model>,
     pub extras: HashSet<extras::Model>,
 }
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoShowPolicyRequest {
+    pub expire_after_minutes: i32,
+    pub warn_after: i32,
+    pub block_after: i32,
+    pub block_days: i32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoShowUser {
+    pub user_id: i32,
+    pub username: String,
+    pub email: String,
+    pub no_shows: i32,
+    #[serde(with = "ts_seconds_option")]
+    pub blocked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoShowUsers {
+    pub users: Vec<NoShowUser>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+    Html,
+}
+
+//days are local dates, both ends inclusive, today and the following week by default
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    #[serde(default)]
+    pub by_slot: bool,
+    #[serde(default)]
+    pub format: ReportFormat,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastItem {
+    pub id: i32,
+    pub name: String,
+    pub portions: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastSlot {
+    //orders placed before pickup slots existed have none
+    pub slot_id: Option<i32>,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub dinners: Vec<ForecastItem>,
+    pub extras: Vec<ForecastItem>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastDay {
+    pub day: NaiveDate,
+    pub dinners: Vec<ForecastItem>,
+    pub extras: Vec<ForecastItem>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub slots: Vec<ForecastSlot>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub by_slot: bool,
+    pub days: Vec<ForecastDay>,
+}
+
+//wallet history filters, days are local dates and both ends are inclusive
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionFilter {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub kind: Option<Vec<TransactionKind>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletTransactionResponse {
+    pub transaction_id: i32,
+    pub kind: TransactionKind,
+    //grosze, negative when money left the wallet
+    pub amount: i64,
+    pub balance: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_intent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct WalletTransactions {
+    pub transactions: Vec<WalletTransactionResponse>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdjustBalanceRequest {
+    //grosze, negative takes money away
+    pub amount: i64,
+    pub note: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceAdjusted {
+    pub user_id: i32,
+    pub amount: i64,
+    pub balance: i64,
+}
+
+#[derive(Deserialize, Default)]
+pub struct WebhookEventFilter {
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub status: Option<Vec<WebhookStatus>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookEventResponse {
+    pub id: i32,
+    pub event_id: String,
+    pub event_type: String,
+    pub status: WebhookStatus,
+    pub attempts: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(with = "ts_seconds")]
+    pub received_at: DateTime<Utc>,
+    #[serde(with = "ts_seconds_option")]
+    pub processed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct WebhookEvents {
+    pub events: Vec<WebhookEventResponse>,
+}
+
+#[derive(Serialize)]
+pub struct WebhookReplayed {
+    pub id: i32,
+    pub status: WebhookStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CardRefundRequest {
+    //grosze, the whole balance when missing
+    pub amount: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardRefundPart {
+    pub payment_intent_id: String,
+    pub refund_id: String,
+    pub amount: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardRefund {
+    //may be less than requested when not enough of the balance was paid by card
+    pub refunded: i64,
+    pub balance: i64,
+    pub refunds: Vec<CardRefundPart>,
+}
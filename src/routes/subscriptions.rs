@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use actix_web::{delete, get, post, web};
+use chrono::{Duration, Local, Utc};
+use entity::{extras, model_enums::Weekday, subscription, subscription_day, subscription_extras};
+use sea_orm::{
+    ActiveEnum, ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait,
+};
+
+use crate::{
+    appstate::AppState,
+    errors::ServiceError,
+    jobs::run_subscriptions,
+    jwt_auth::AuthUser,
+    map_db_err,
+    routes::structs::{SubscriptionDayResponse, SubscriptionRequest, SubscriptionResponse},
+    subscriptions::week_day_bit,
+};
+
+#[get("/")]
+async fn get_subscriptions(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<web::Json<Vec<SubscriptionResponse>>, ServiceError> {
+    let conn = &data.conn;
+    let subscriptions = subscription::Entity::find()
+        .filter(subscription::Column::UserId.eq(user.id))
+        .order_by_asc(subscription::Column::Id)
+        .find_with_related(subscription_extras::Entity)
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+
+    let since = Local::now().date_naive() - Duration::days(14);
+    let mut days: HashMap<i32, Vec<SubscriptionDayResponse>> = HashMap::new();
+    let history = subscription_day::Entity::find()
+        .filter(
+            subscription_day::Column::SubscriptionId.is_in(subscriptions.iter().map(|(s, _)| s.id)),
+        )
+        .filter(subscription_day::Column::Day.gte(since))
+        .order_by_asc(subscription_day::Column::Day)
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+    for day in history {
+        days.entry(day.subscription_id)
+            .or_default()
+            .push(SubscriptionDayResponse {
+                day: day.day,
+                order_id: day.order_id,
+                failure: day.failure,
+            });
+    }
+
+    Ok(web::Json(
+        subscriptions
+            .into_iter()
+            .map(|(s, extras)| SubscriptionResponse {
+                subscription_id: s.id,
+                week_days: (0..6)
+                    .filter(|wd| s.week_days & week_day_bit(*wd) != 0)
+                    .filter_map(|wd| Weekday::try_from_value(&wd).ok())
+                    .collect(),
+                pickup_time: s.pickup_time,
+                with_soup: s.with_soup != 0,
+                active: s.active != 0,
+                extras_ids: extras.into_iter().map(|e| e.extras_id).collect(),
+                days: days.remove(&s.id).unwrap_or_default(),
+            })
+            .collect(),
+    ))
+}
+
+#[post("/")]
+async fn create_subscription(
+    user: AuthUser,
+    data: web::Data<AppState>,
+    body: web::Json<SubscriptionRequest>,
+) -> Result<String, ServiceError> {
+    let body = body.into_inner();
+    let week_days = body
+        .week_days
+        .iter()
+        .fold(0, |bits, wd| bits | week_day_bit(wd.clone() as u8));
+    if week_days == 0 {
+        return Err(ServiceError::BadRequest(
+            "Subscription needs at least one week day".into(),
+        ));
+    }
+
+    let conn = &data.conn;
+    let mut extras_ids = body.extras_ids;
+    extras_ids.sort();
+    extras_ids.dedup();
+    let known = extras::Entity::find()
+        .filter(extras::Column::Id.is_in(extras_ids.clone()))
+        .count(conn)
+        .await
+        .map_err(map_db_err)?;
+    if known != extras_ids.len() as u64 {
+        return Err(ServiceError::BadRequest("No extra has given id".into()));
+    }
+
+    let txn = conn.begin().await.map_err(map_db_err)?;
+    let subscription = subscription::ActiveModel {
+        user_id: Set(user.id),
+        week_days: Set(week_days),
+        pickup_time: Set(body.pickup_time),
+        with_soup: Set(body.with_soup as i8),
+        active: Set(1),
+        created_at: Set(Utc::now()),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await
+    .map_err(map_db_err)?;
+
+    let extras = extras_ids
+        .into_iter()
+        .map(|extras_id| subscription_extras::ActiveModel {
+            subscription_id: Set(subscription.id),
+            extras_id: Set(extras_id),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    if !extras.is_empty() {
+        subscription_extras::Entity::insert_many(extras)
+            .exec(&txn)
+            .await
+            .map_err(map_db_err)?;
+    }
+    txn.commit().await.map_err(map_db_err)?;
+
+    //remaining days of the current menu are ordered right away
+    run_subscriptions(data);
+
+    Ok("Success".into())
+}
+
+//stops future orders, the ones already placed stay and can be cancelled one by one
+#[delete("/{id}")]
+async fn cancel_subscription(
+    user: AuthUser,
+    path: web::Path<i32>,
+    data: web::Data<AppState>,
+) -> Result<String, ServiceError> {
+    let res = subscription::Entity::update_many()
+        .col_expr(subscription::Column::Active, 0.into())
+        .filter(subscription::Column::Id.eq(path.into_inner()))
+        .filter(subscription::Column::UserId.eq(user.id))
+        .exec(&data.conn)
+        .await
+        .map_err(map_db_err)?;
+
+    if res.rows_affected == 0 {
+        return Err(ServiceError::NotFound(
+            "No subscription has given id".into(),
+        ));
+    }
+    Ok("Success".into())
+}
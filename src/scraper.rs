@@ -27,8 +27,11 @@ impl MenuDay {
     }
 }
 
-fn get_menu() -> Result<String, ureq::Error> {
-    let html = ureq::get(MENU_URL).call()?.into_string()?;
+fn get_menu() -> Result<String, Box<ureq::Error>> {
+    let html = ureq::get(MENU_URL)
+        .call()?
+        .into_string()
+        .map_err(ureq::Error::from)?;
 
     Ok(html)
 }
@@ -61,8 +64,7 @@ fn vec_to_menu(mut vec: Vec<Vec<String>>) -> Vec<MenuDay> {
             }
             if TWO_PARTS_DISHES_PREFIXES
                 .iter()
-                .map(|prefix| curr_dish.starts_with(prefix))
-                .any(|bl| bl)
+                .any(|prefix| curr_dish.starts_with(prefix))
             {
                 if let Some(last_dish) = menu_days[idx].dishes.last_mut() {
                     last_dish.push(' ');
@@ -141,7 +143,7 @@ pub async fn scrape_menu() -> Result<Vec<(u8, MenuDay)>, ServiceError> {
         if is_wed {
             thu_to_sat.push(vec);
         } else {
-            if let Some(txt) = vec.get(0) {
+            if let Some(txt) = vec.first() {
                 if txt == "CZWARTEK" {
                     is_wed = true;
                     continue;
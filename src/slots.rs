@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use entity::{dinner_orders, model_enums::Status, pickup_slot};
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+
+use crate::{
+    errors::ServiceError,
+    map_db_err,
+    stock::{day_bounds, local_to_utc},
+};
+
+//slot's start on given local day, stored as the order's collection_date
+pub fn slot_start(day: NaiveDate, slot: &pickup_slot::Model) -> DateTime<Utc> {
+    local_to_utc(day, slot.start_time)
+}
+
+pub fn slot_end(day: NaiveDate, slot: &pickup_slot::Model) -> DateTime<Utc> {
+    local_to_utc(day, slot.end_time)
+}
+
+//active slots served on given day, earliest first
+pub async fn slots_for_day<C>(
+    conn: &C,
+    day: NaiveDate,
+) -> Result<Vec<pickup_slot::Model>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    pickup_slot::Entity::find()
+        .filter(pickup_slot::Column::WeekDay.eq(day.weekday().num_days_from_monday() as u8))
+        .filter(pickup_slot::Column::Active.eq(1))
+        .order_by_asc(pickup_slot::Column::StartTime)
+        .all(conn)
+        .await
+        .map_err(map_db_err)
+}
+
+//Number of orders taking place in each slot on given day, cancelled orders free their place
+pub async fn slot_usage<C>(conn: &C, day: NaiveDate) -> Result<HashMap<i32, i64>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let (start, end) = day_bounds(day);
+    let usage: Vec<(Option<i32>, i64)> = dinner_orders::Entity::find()
+        .select_only()
+        .column(dinner_orders::Column::SlotId)
+        .column_as(dinner_orders::Column::Id.count(), "count")
+        .filter(dinner_orders::Column::SlotId.is_not_null())
+        .filter(dinner_orders::Column::CollectionDate.gte(start))
+        .filter(dinner_orders::Column::CollectionDate.lt(end))
+        .filter(dinner_orders::Column::Status.ne(Status::Cancelled))
+        .group_by(dinner_orders::Column::SlotId)
+        .into_tuple()
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(usage
+        .into_iter()
+        .filter_map(|(slot_id, count)| Some((slot_id?, count)))
+        .collect())
+}
+
+//Takes one place in the slot for given day.
+//Has to run inside the order's transaction, the slot row stays locked until commit
+//so two orders can't both take the last place.
+pub async fn reserve_slot<C>(
+    conn: &C,
+    slot_id: i32,
+    day: NaiveDate,
+) -> Result<pickup_slot::Model, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let slot = pickup_slot::Entity::find_by_id(slot_id)
+        .lock_exclusive()
+        .one(conn)
+        .await
+        .map_err(map_db_err)?
+        .ok_or_else(|| ServiceError::BadRequest("No pickup slot has given id".into()))?;
+
+    let taken = slot_usage(conn, day)
+        .await?
+        .get(&slot.id)
+        .copied()
+        .unwrap_or(0);
+    if taken >= slot.capacity as i64 {
+        return Err(ServiceError::BadRequest(format!(
+            "Pickup slot {}-{} on {} is full",
+            slot.start_time.format("%H:%M"),
+            slot.end_time.format("%H:%M"),
+            day
+        )));
+    }
+
+    Ok(slot)
+}
@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc,
+};
+use entity::{dinner, dinner_stock};
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, Set,
+};
+
+use crate::{errors::ServiceError, map_db_err};
+
+//stock is counted per local calendar day, not per utc timestamp
+pub fn collection_day(collection_date: &DateTime<Utc>) -> NaiveDate {
+    collection_date.with_timezone(&Local).date_naive()
+}
+
+//utc range [start, end) covering given local day
+pub fn day_bounds(day: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    day_bounds_in(&Local, day)
+}
+
+fn day_bounds_in<Tz: TimeZone>(tz: &Tz, day: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = |day: NaiveDate| to_utc(tz, day.and_time(NaiveTime::MIN));
+    (start(day), start(day + Duration::days(1)))
+}
+
+//local time of given day as utc
+pub fn local_to_utc(day: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    to_utc(&Local, day.and_time(time))
+}
+
+//Time skipped when clocks move forward (even midnight in some zones) doesn't exist locally,
+//it's taken as the first moment after the gap. Repeated time is the earlier of the two
+fn to_utc<Tz: TimeZone>(tz: &Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    (0..=8)
+        .find_map(|step| {
+            tz.from_local_datetime(&(local + Duration::minutes(15 * step)))
+                .earliest()
+        })
+        .map(|time| time.with_timezone(&Utc))
+        //gaps never span two hours, this only keeps a broken zone from panicking
+        .unwrap_or_else(|| {
+            let offset = tz.offset_from_utc_datetime(&local).fix();
+            DateTime::from_utc(local - offset, Utc)
+        })
+}
+
+//closest date (today included) falling on given menu week_day (0 = monday)
+pub fn date_for_week_day(week_day: u8) -> NaiveDate {
+    let today = Local::now().date_naive();
+    let today_idx = today.weekday().num_days_from_monday() as i64;
+    let diff = (7 + week_day as i64 - today_idx) % 7;
+
+    today + Duration::days(diff)
+}
+
+fn count_portions(dinner_ids: &[i32]) -> BTreeMap<i32, i32> {
+    //BTreeMap so rows are always locked in the same order
+    let mut portions = BTreeMap::new();
+    for id in dinner_ids {
+        *portions.entry(*id).or_insert(0) += 1;
+    }
+    portions
+}
+
+//Reserves one portion per entry in `dinner_ids` for given day.
+//Has to run inside the order's transaction, the upsert keeps the stock row locked until commit
+//and an error here leaves the counters to be rolled back with the rest of the order.
+pub async fn reserve_stock<C>(
+    conn: &C,
+    day: NaiveDate,
+    dinner_ids: &[i32],
+) -> Result<(), ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let portions = count_portions(dinner_ids);
+
+    for (dinner_id, amount) in portions.iter() {
+        dinner_stock::Entity::insert(dinner_stock::ActiveModel {
+            dinner_id: Set(*dinner_id),
+            day: Set(day),
+            reserved: Set(*amount),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([dinner_stock::Column::DinnerId, dinner_stock::Column::Day])
+                .value(
+                    dinner_stock::Column::Reserved,
+                    Expr::col(dinner_stock::Column::Reserved).add(*amount),
+                )
+                .to_owned(),
+        )
+        .exec_without_returning(conn)
+        .await
+        .map_err(map_db_err)?;
+    }
+
+    let stock = dinner_stock::Entity::find()
+        .filter(dinner_stock::Column::Day.eq(day))
+        .filter(dinner_stock::Column::DinnerId.is_in(portions.keys().copied()))
+        .find_also_related(dinner::Entity)
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+
+    let sold_out = stock
+        .into_iter()
+        .filter_map(|(stock, dinner)| {
+            let dinner = dinner?;
+            if stock.reserved <= dinner.max_supply {
+                return None;
+            }
+            let left = (dinner.max_supply - (stock.reserved - portions[&dinner.id])).max(0);
+            Some(format!("{} (portions left: {})", dinner.name, left))
+        })
+        .collect::<Vec<_>>();
+
+    if !sold_out.is_empty() {
+        return Err(ServiceError::BadRequest(format!(
+            "Sold out for {}: {}",
+            day,
+            sold_out.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+//Gives back portions reserved by `reserve_stock`, e.g. when an order gets cancelled
+pub async fn release_stock<C>(
+    conn: &C,
+    day: NaiveDate,
+    dinner_ids: &[i32],
+) -> Result<(), ServiceError>
+where
+    C: ConnectionTrait,
+{
+    for (dinner_id, amount) in count_portions(dinner_ids) {
+        dinner_stock::Entity::update_many()
+            .col_expr(
+                dinner_stock::Column::Reserved,
+                Expr::col(dinner_stock::Column::Reserved).sub(amount),
+            )
+            .filter(dinner_stock::Column::DinnerId.eq(dinner_id))
+            .filter(dinner_stock::Column::Day.eq(day))
+            .exec(conn)
+            .await
+            .map_err(map_db_err)?;
+    }
+
+    Ok(())
+}
+
+//Reserved portions of given dinners, keyed by (dinner_id, day)
+pub async fn reserved_portions<C>(
+    conn: &C,
+    dinner_ids: impl IntoIterator<Item = i32>,
+    days: impl IntoIterator<Item = NaiveDate>,
+) -> Result<HashMap<(i32, NaiveDate), i32>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let stock = dinner_stock::Entity::find()
+        .filter(dinner_stock::Column::DinnerId.is_in(dinner_ids))
+        .filter(dinner_stock::Column::Day.is_in(days))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(stock
+        .into_iter()
+        .map(|stock| ((stock.dinner_id, stock.day), stock.reserved))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, LocalResult};
+    use entity::sea_orm_active_enums::Type;
+    use rust_decimal::Decimal;
+    use sea_orm::{DbBackend, MockDatabase, MockExecResult};
+
+    use super::*;
+
+    //Zone at utc+1 moving its clocks an hour forward at `.0` local time, like Europe does
+    //at 2:00 in March. Some zones did it at midnight
+    #[derive(Clone, Copy, Debug)]
+    struct SpringForward(NaiveDateTime);
+
+    impl SpringForward {
+        fn winter() -> FixedOffset {
+            FixedOffset::east_opt(3600).unwrap()
+        }
+
+        fn summer() -> FixedOffset {
+            FixedOffset::east_opt(7200).unwrap()
+        }
+    }
+
+    impl TimeZone for SpringForward {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            unimplemented!("only used to convert into the zone")
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            if *local < self.0 {
+                LocalResult::Single(Self::winter())
+            } else if *local < self.0 + Duration::hours(1) {
+                LocalResult::None
+            } else {
+                LocalResult::Single(Self::summer())
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            if *utc < self.0 - Duration::hours(1) {
+                Self::winter()
+            } else {
+                Self::summer()
+            }
+        }
+    }
+
+    fn at(day: NaiveDate, hour: u32, min: u32) -> NaiveDateTime {
+        day.and_hms_opt(hour, min, 0).unwrap()
+    }
+
+    fn change_day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 3, 26).unwrap()
+    }
+
+    #[test]
+    fn time_skipped_by_the_clock_change_moves_past_it() {
+        let day = change_day();
+        let zone = SpringForward(at(day, 2, 0));
+
+        assert_eq!(to_utc(&zone, at(day, 1, 30)).naive_utc(), at(day, 0, 30));
+        assert_eq!(to_utc(&zone, at(day, 2, 30)).naive_utc(), at(day, 1, 0));
+        assert_eq!(to_utc(&zone, at(day, 3, 30)).naive_utc(), at(day, 1, 30));
+    }
+
+    #[test]
+    fn day_without_midnight_starts_after_the_gap() {
+        let day = change_day();
+        let zone = SpringForward(at(day, 0, 0));
+
+        let (start, end) = day_bounds_in(&zone, day);
+
+        assert_eq!(start.naive_utc(), at(day - Duration::days(1), 23, 0));
+        assert_eq!(end.naive_utc(), at(day, 22, 0));
+        assert_eq!(day_bounds_in(&zone, day - Duration::days(1)).1, start);
+    }
+
+    fn dinner(id: i32, max_supply: i32) -> dinner::Model {
+        dinner::Model {
+            id,
+            name: format!("Dinner {}", id),
+            price: Decimal::new(1500, 2),
+            image: String::new(),
+            week_day: 0,
+            max_supply,
+            r#type: Type::Main,
+        }
+    }
+
+    fn stock(dinner_id: i32, day: NaiveDate, reserved: i32) -> dinner_stock::Model {
+        dinner_stock::Model {
+            id: dinner_id,
+            dinner_id,
+            day,
+            reserved,
+        }
+    }
+
+    fn upserted() -> MockExecResult {
+        MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 1,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn last_portions_can_be_reserved() {
+        let day = change_day();
+        let conn = MockDatabase::new(DbBackend::MySql)
+            .append_exec_results([upserted()])
+            .append_query_results([[(stock(1, day, 50), dinner(1, 50))]])
+            .into_connection();
+
+        reserve_stock(&conn, day, &[1, 1]).await.unwrap();
+
+        let log = format!("{:?}", conn.into_transaction_log());
+        assert!(log.contains("ON DUPLICATE KEY UPDATE"), "{}", log);
+    }
+
+    #[actix_rt::test]
+    async fn sold_out_dinner_is_rejected() {
+        let day = change_day();
+        let conn = MockDatabase::new(DbBackend::MySql)
+            .append_exec_results([upserted(), upserted()])
+            .append_query_results([[
+                (stock(1, day, 51), dinner(1, 50)),
+                (stock(2, day, 3), dinner(2, 50)),
+            ]])
+            .into_connection();
+
+        let result = reserve_stock(&conn, day, &[1, 1, 2]).await;
+
+        match result {
+            Err(ServiceError::BadRequest(msg)) => {
+                assert_eq!(
+                    msg,
+                    format!("Sold out for {}: Dinner 1 (portions left: 1)", day)
+                )
+            }
+            _ => panic!("sold out dinner was reserved"),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn released_portions_are_taken_off_the_counter() {
+        let day = change_day();
+        let conn = MockDatabase::new(DbBackend::MySql)
+            .append_exec_results([upserted(), upserted()])
+            .into_connection();
+
+        release_stock(&conn, day, &[2, 1, 2]).await.unwrap();
+
+        //one update per dinner taking all of its portions off at once
+        let log = format!("{:?}", conn.into_transaction_log());
+        let released = log.split("Statement { ").skip(1).collect::<Vec<_>>();
+        assert_eq!(released.len(), 2, "{}", log);
+        assert!(
+            released[0].contains("Int(Some(1)), Int(Some(1))"),
+            "{}",
+            log
+        );
+        assert!(
+            released[1].contains("Int(Some(2)), Int(Some(2))"),
+            "{}",
+            log
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Datelike, Duration, Local, NaiveDate, Utc};
+use entity::{
+    dinner, extras, extras_dinner, menu_info, pickup_slot, sea_orm_active_enums::Type,
+    subscription, subscription_day, subscription_extras, user,
+};
+use log::{error, info};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set,
+};
+
+use crate::{
+    appstate::AppState,
+    errors::ServiceError,
+    map_db_err,
+    routes::{
+        favourites::dish_key,
+        order::{order_cutoff, place_order},
+        structs::{Dinner, OrderRequest},
+    },
+    send_mail,
+    slots::{slot_start, slots_for_day},
+    stock::reserved_portions,
+};
+
+//bit of given menu week_day in subscription.week_days
+pub fn week_day_bit(week_day: u8) -> u8 {
+    1 << week_day
+}
+
+//errors of the app itself (e.g. a lost db connection) rather than of the order,
+//the day is retried instead of being failed for good
+fn temporary(err: &ServiceError) -> bool {
+    matches!(err, ServiceError::InternalError)
+}
+
+//Dates the menu's week days fall on. The menu only knows week days, its week is the one
+//where the last of them comes next after the menu was scraped, so a menu fetched on Sunday
+//belongs to the following week and one fetched midweek to the current one
+fn menu_week(scraped: NaiveDate, week_days: &[u8]) -> Vec<NaiveDate> {
+    let Some(last) = week_days.iter().max().copied() else {
+        return Vec::new();
+    };
+    let scraped_idx = scraped.weekday().num_days_from_monday() as i64;
+    let last_day = scraped + Duration::days((7 + last as i64 - scraped_idx) % 7);
+    let monday = last_day - Duration::days(last as i64);
+
+    let mut days = week_days
+        .iter()
+        .map(|week_day| monday + Duration::days(*week_day as i64))
+        .collect::<Vec<_>>();
+    days.sort();
+    days.dedup();
+    days
+}
+
+//Days of the stored menu's week that can still be ordered, today included
+async fn menu_days<C>(conn: &C) -> Result<Vec<NaiveDate>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let Some(info) = menu_info::Entity::find()
+        .one(conn)
+        .await
+        .map_err(map_db_err)?
+    else {
+        return Ok(Vec::new());
+    };
+    let week_days: Vec<u8> = dinner::Entity::find()
+        .select_only()
+        .column(dinner::Column::WeekDay)
+        .distinct()
+        .into_tuple()
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+    let scraped = info.last_update.with_timezone(&Local).date_naive();
+    let today = Local::now().date_naive();
+
+    Ok(menu_week(scraped, &week_days)
+        .into_iter()
+        .filter(|day| *day >= today)
+        .collect())
+}
+
+//Places orders for every active subscription on every upcoming day of the stored menu.
+//Each (subscription, day) is handled once, failures are recorded and mailed to the user
+pub async fn materialise_subscriptions(data: &AppState) -> Result<(), ServiceError> {
+    let conn = &data.conn;
+    let days = menu_days(conn).await?;
+    if days.is_empty() {
+        return Ok(());
+    }
+
+    let subscriptions = subscription::Entity::find()
+        .filter(subscription::Column::Active.eq(1))
+        .find_with_related(subscription_extras::Entity)
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+    if subscriptions.is_empty() {
+        return Ok(());
+    }
+
+    let done: HashSet<(i32, NaiveDate)> = subscription_day::Entity::find()
+        .filter(
+            subscription_day::Column::SubscriptionId.is_in(subscriptions.iter().map(|s| s.0.id)),
+        )
+        .filter(subscription_day::Column::Day.is_in(days.clone()))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|day| (day.subscription_id, day.day))
+        .collect();
+
+    for day in days {
+        let week_day = day.weekday().num_days_from_monday() as u8;
+        let slots = slots_for_day(conn, day).await?;
+
+        for (subscription, extras) in subscriptions.iter() {
+            if subscription.week_days & week_day_bit(week_day) == 0
+                || done.contains(&(subscription.id, day))
+            {
+                continue;
+            }
+
+            //slot containing preferred time, first one of the day otherwise
+            let slot = slots
+                .iter()
+                .find(|s| {
+                    s.start_time <= subscription.pickup_time
+                        && subscription.pickup_time < s.end_time
+                })
+                .or(slots.first());
+            let too_late = match slot {
+                Some(slot) => Utc::now() >= order_cutoff(&slot_start(day, slot))?,
+                None => false,
+            };
+            if too_late {
+                continue;
+            }
+
+            let extras_ids = extras.iter().map(|e| e.extras_id).collect::<Vec<_>>();
+            if let Err(err) = materialise_day(data, subscription, &extras_ids, slot, day).await {
+                error!(
+                    "Subscription {} for {} failed: {}",
+                    subscription.id, day, err
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn materialise_day(
+    data: &AppState,
+    subscription: &subscription::Model,
+    extras_ids: &[i32],
+    slot: Option<&pickup_slot::Model>,
+    day: NaiveDate,
+) -> Result<(), ServiceError> {
+    let conn = &data.conn;
+
+    //claimed before ordering so a concurrent run can't charge the same day twice,
+    //the unique (subscription_id, day) index makes the second insert fail
+    let claim = subscription_day::ActiveModel {
+        subscription_id: Set(subscription.id),
+        day: Set(day),
+        created_at: Set(Utc::now()),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await
+    .map_err(map_db_err)?;
+
+    let result = match slot {
+        None => Err(ServiceError::BadRequest(format!(
+            "Canteen is closed on {}",
+            day
+        ))),
+        Some(slot) => match subscription_lines(conn, subscription, extras_ids, day).await {
+            Ok(dinners) => {
+                let order = OrderRequest {
+                    dinners,
+                    collection_day: day,
+                    slot_id: slot.id,
+                    note: None,
+                };
+                place_order(data, subscription.user_id, order, false).await
+            }
+            Err(err) => Err(err),
+        },
+    };
+
+    let claim_id = claim.id;
+    let mut claim: subscription_day::ActiveModel = claim.into();
+    match result {
+        Ok(created) => {
+            info!(
+                "Subscription {} ordered {} as order {}",
+                subscription.id, day, created.order_id
+            );
+            claim.order_id = Set(Some(created.order_id));
+            claim.update(conn).await.map_err(map_db_err)?;
+        }
+        Err(err) if temporary(&err) => {
+            //nothing was ordered, the day is given back so the next run tries it again
+            subscription_day::Entity::delete_by_id(claim_id)
+                .exec(conn)
+                .await
+                .map_err(map_db_err)?;
+            return Err(err);
+        }
+        Err(err) => {
+            let reason = err.to_string();
+            claim.failure = Set(Some(reason.chars().take(255).collect()));
+            claim.update(conn).await.map_err(map_db_err)?;
+            notify_failure(conn, subscription.user_id, day, &reason).await?;
+        }
+    }
+
+    Ok(())
+}
+
+//Newest main dish (and soup) of the day that still has portions left,
+//subscribed extras are matched by name with the ones served with that dish
+async fn subscription_lines<C>(
+    conn: &C,
+    subscription: &subscription::Model,
+    extras_ids: &[i32],
+    day: NaiveDate,
+) -> Result<Vec<Dinner>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let week_day = day.weekday().num_days_from_monday() as u8;
+    let menu = dinner::Entity::find()
+        .filter(dinner::Column::WeekDay.eq(week_day))
+        .order_by_desc(dinner::Column::Id)
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+    if menu.is_empty() {
+        return Err(ServiceError::BadRequest(format!(
+            "Canteen is closed on {}",
+            day
+        )));
+    }
+
+    let reserved = reserved_portions(conn, menu.iter().map(|d| d.id), [day]).await?;
+    let available = |dinner: &&dinner::Model| {
+        dinner.max_supply > reserved.get(&(dinner.id, day)).copied().unwrap_or(0)
+    };
+
+    let Some(main) = menu
+        .iter()
+        .filter(|d| d.r#type == Type::Main)
+        .find(available)
+    else {
+        return Err(ServiceError::BadRequest(format!(
+            "All main dishes are sold out on {}",
+            day
+        )));
+    };
+
+    let wanted: HashSet<_> = extras::Entity::find()
+        .filter(extras::Column::Id.is_in(extras_ids.to_vec()))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .iter()
+        .map(|e| dish_key(&e.name))
+        .collect();
+    let offered: HashMap<_, _> = extras_dinner::Entity::find()
+        .filter(extras_dinner::Column::DinnerId.eq(main.id))
+        .find_also_related(extras::Entity)
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .filter_map(|(_, extra)| extra.map(|e| (dish_key(&e.name), e.id)))
+        .collect();
+
+    let mut lines = vec![Dinner {
+        dinner_id: main.id,
+        extras_ids: wanted
+            .iter()
+            .filter_map(|name| offered.get(name).copied())
+            .collect(),
+        note: None,
+        dietary_flags: Vec::new(),
+        attendee_user_id: None,
+        attendee_name: None,
+    }];
+
+    if subscription.with_soup != 0 {
+        let soup = menu
+            .iter()
+            .filter(|d| d.r#type == Type::Soup)
+            .find(available);
+        match soup {
+            Some(soup) => lines.push(Dinner {
+                dinner_id: soup.id,
+                extras_ids: Vec::new(),
+                note: None,
+                dietary_flags: Vec::new(),
+                attendee_user_id: None,
+                attendee_name: None,
+            }),
+            None => {
+                return Err(ServiceError::BadRequest(format!(
+                    "Soup is sold out on {}",
+                    day
+                )))
+            }
+        }
+    }
+
+    Ok(lines)
+}
+
+async fn notify_failure<C>(
+    conn: &C,
+    user_id: i32,
+    day: NaiveDate,
+    reason: &str,
+) -> Result<(), ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let user = user::Entity::find_by_id(user_id)
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    let Some(user) = user else {
+        return Ok(());
+    };
+
+    send_mail(
+        &user.email,
+        "Kantyna - zamówienie z subskrypcji",
+        format!(
+            "Nie udało się złożyć zamówienia z subskrypcji na {}.\nPowód: {}",
+            day, reason
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 5, day).unwrap()
+    }
+
+    #[test]
+    fn menu_week_comes_from_its_days() {
+        let monday_to_saturday = [0, 1, 2, 3, 4, 5];
+        let week = (15..=20).map(date).collect::<Vec<_>>();
+
+        //scraped on sunday, midweek and on the last day of the menu
+        for scraped in [14, 17, 20] {
+            assert_eq!(menu_week(date(scraped), &monday_to_saturday), week);
+        }
+        //canteen closed on thursday and open on sunday, menu scraped on monday
+        assert_eq!(
+            menu_week(date(15), &[6, 0, 1, 2, 4, 5]),
+            [15, 16, 17, 19, 20, 21].map(date)
+        );
+        assert!(menu_week(date(17), &[]).is_empty());
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Datelike, Utc};
+use entity::{dinner, extras, extras_dinner, pickup_slot, user};
+use sea_orm::{
+    prelude::Decimal, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+};
+
+use crate::{
+    errors::ServiceError,
+    map_db_err,
+    routes::structs::OrderRequest,
+    slots::{slot_end, slot_start},
+};
+
+pub const NOTE_MAX_LEN: usize = 200;
+pub const ATTENDEE_NAME_MAX_LEN: usize = 64;
+
+//blank notes and attendee names are dropped so the kitchen only sees real requests
+pub fn clean_notes(order: &mut OrderRequest) {
+    let clean = |note: &mut Option<String>| {
+        *note = note
+            .take()
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty());
+    };
+
+    clean(&mut order.note);
+    for line in order.dinners.iter_mut() {
+        clean(&mut line.note);
+        clean(&mut line.attendee_name);
+    }
+}
+
+//menu rows referenced by a validated order, reused later for pricing
+pub struct OrderContents {
+    pub dinners: HashMap<i32, dinner::Model>,
+    pub extras: HashMap<i32, extras::Model>,
+    pub slot: pickup_slot::Model,
+    //start of the chosen slot
+    pub collection_date: DateTime<Utc>,
+}
+
+impl OrderContents {
+    //exact total of the validated order, every id is known to be present here
+    pub fn price(&self, order: &OrderRequest) -> Decimal {
+        order
+            .dinners
+            .iter()
+            .map(|line| {
+                let extras = line
+                    .extras_ids
+                    .iter()
+                    .map(|id| self.extras[id].price)
+                    .sum::<Decimal>();
+                self.dinners[&line.dinner_id].price + extras
+            })
+            .sum()
+    }
+}
+
+//Checks order against the menu of its collection day.
+//Doesn't stop at first problem, everything found is returned in one ServiceError::BadRequest
+//separated with "; ". Only group orders may attribute lines to other people
+pub async fn validate_order<C>(
+    conn: &C,
+    order: &OrderRequest,
+    group: bool,
+) -> Result<OrderContents, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let mut problems: Vec<String> = Vec::new();
+
+    if order.dinners.is_empty() {
+        problems.push("Order has to contain at least one dinner".to_string());
+    }
+
+    if order
+        .note
+        .as_ref()
+        .is_some_and(|n| n.chars().count() > NOTE_MAX_LEN)
+    {
+        problems.push(format!(
+            "Order note can't be longer than {} characters",
+            NOTE_MAX_LEN
+        ));
+    }
+
+    //menu week_day uses the same numbering as chrono (0 = monday)
+    let day = order.collection_day;
+    let week_day = day.weekday().num_days_from_monday() as u8;
+
+    let slot = pickup_slot::Entity::find_by_id(order.slot_id)
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    match &slot {
+        None => problems.push("No pickup slot has given id".to_string()),
+        Some(slot) if slot.active == 0 => {
+            problems.push("Pickup slot is no longer available".to_string())
+        }
+        Some(slot) if slot.week_day != week_day => {
+            problems.push(format!("Pickup slot isn't available on {}", day))
+        }
+        Some(slot) if slot_end(day, slot) < Utc::now() => {
+            problems.push("Collection date is in the past".to_string())
+        }
+        Some(_) => {}
+    }
+    let dinners_that_day = dinner::Entity::find()
+        .filter(dinner::Column::WeekDay.eq(week_day))
+        .count(conn)
+        .await
+        .map_err(map_db_err)?;
+    if dinners_that_day == 0 {
+        problems.push(format!("Canteen is closed on {}", day));
+    }
+
+    let dinner_ids: HashSet<_> = order.dinners.iter().map(|x| x.dinner_id).collect();
+    let extras_ids: HashSet<_> = order
+        .dinners
+        .iter()
+        .flat_map(|x| x.extras_ids.iter().copied())
+        .collect();
+
+    let dinners: HashMap<_, _> = dinner::Entity::find()
+        .filter(dinner::Column::Id.is_in(dinner_ids.iter().copied()))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|dinner| (dinner.id, dinner))
+        .collect();
+    let extras: HashMap<_, _> = extras::Entity::find()
+        .filter(extras::Column::Id.is_in(extras_ids.iter().copied()))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|extra| (extra.id, extra))
+        .collect();
+    let offered: HashSet<(i32, i32)> = extras_dinner::Entity::find()
+        .filter(extras_dinner::Column::DinnerId.is_in(dinner_ids.iter().copied()))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|link| (link.dinner_id, link.extras_id))
+        .collect();
+    let attendees: HashSet<i32> = user::Entity::find()
+        .filter(user::Column::Id.is_in(order.dinners.iter().filter_map(|x| x.attendee_user_id)))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|user| user.id)
+        .collect();
+
+    for line in order.dinners.iter() {
+        if !group && (line.attendee_user_id.is_some() || line.attendee_name.is_some()) {
+            problems.push("Only group orders can be placed for other people".to_string());
+        }
+        if let Some(id) = line.attendee_user_id.filter(|id| !attendees.contains(id)) {
+            problems.push(format!("No user has id {}", id));
+        }
+        if line
+            .attendee_name
+            .as_ref()
+            .is_some_and(|n| n.chars().count() > ATTENDEE_NAME_MAX_LEN)
+        {
+            problems.push(format!(
+                "Attendee name can't be longer than {} characters",
+                ATTENDEE_NAME_MAX_LEN
+            ));
+        }
+
+        match dinners.get(&line.dinner_id) {
+            None => problems.push(format!("No dinner has id {}", line.dinner_id)),
+            Some(dinner) if dinner.week_day != week_day => {
+                problems.push(format!("{} is not served on {}", dinner.name, day))
+            }
+            Some(_) => {}
+        }
+
+        if line
+            .note
+            .as_ref()
+            .is_some_and(|n| n.chars().count() > NOTE_MAX_LEN)
+        {
+            problems.push(format!(
+                "Dish note can't be longer than {} characters",
+                NOTE_MAX_LEN
+            ));
+        }
+
+        for extras_id in line.extras_ids.iter().copied() {
+            match (extras.get(&extras_id), dinners.get(&line.dinner_id)) {
+                (None, _) => problems.push(format!("No extra has id {}", extras_id)),
+                (Some(extra), Some(dinner)) if !offered.contains(&(dinner.id, extras_id)) => {
+                    problems.push(format!("{} isn't served with {}", extra.name, dinner.name))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let Some(slot) = slot.filter(|_| problems.is_empty()) else {
+        return Err(ServiceError::BadRequest(problems.join("; ")));
+    };
+
+    Ok(OrderContents {
+        dinners,
+        extras,
+        collection_date: slot_start(day, &slot),
+        slot,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::{Duration, Local, NaiveDate, NaiveTime};
+    use entity::sea_orm_active_enums::{ExtrasType, Type};
+    use sea_orm::{DbBackend, MockDatabase, Value};
+
+    use super::*;
+    use crate::routes::structs::Dinner;
+
+    const SLOT_ID: i32 = 3;
+
+    fn week_day(day: NaiveDate) -> u8 {
+        day.weekday().num_days_from_monday() as u8
+    }
+
+    fn slot(day: NaiveDate, active: i8) -> pickup_slot::Model {
+        pickup_slot::Model {
+            id: SLOT_ID,
+            week_day: week_day(day),
+            start_time: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            capacity: 20,
+            active,
+        }
+    }
+
+    fn dinner(id: i32, day: NaiveDate) -> dinner::Model {
+        dinner::Model {
+            id,
+            name: format!("Dinner {}", id),
+            price: Decimal::new(1500, 2),
+            image: String::new(),
+            week_day: week_day(day),
+            max_supply: 50,
+            r#type: Type::Main,
+        }
+    }
+
+    fn extra(id: i32) -> extras::Model {
+        extras::Model {
+            id,
+            name: format!("Extra {}", id),
+            price: Decimal::new(300, 2),
+            image: String::new(),
+            r#type: ExtrasType::Beverage,
+        }
+    }
+
+    fn order(day: NaiveDate, lines: &[(i32, &[i32])]) -> OrderRequest {
+        OrderRequest {
+            dinners: lines
+                .iter()
+                .map(|(dinner_id, extras_ids)| Dinner {
+                    dinner_id: *dinner_id,
+                    extras_ids: extras_ids.to_vec(),
+                    note: None,
+                    dietary_flags: Vec::new(),
+                    attendee_user_id: None,
+                    attendee_name: None,
+                })
+                .collect(),
+            collection_day: day,
+            slot_id: SLOT_ID,
+            note: None,
+        }
+    }
+
+    //menu of the order's day as the queries of `validate_order` return it
+    async fn validate(
+        order: &OrderRequest,
+        slot: pickup_slot::Model,
+        dinners: Vec<dinner::Model>,
+        extras: Vec<extras::Model>,
+        offered: &[(i32, i32)],
+    ) -> Result<OrderContents, ServiceError> {
+        let offered = offered
+            .iter()
+            .enumerate()
+            .map(|(id, (dinner_id, extras_id))| extras_dinner::Model {
+                id: id as i32,
+                dinner_id: *dinner_id,
+                extras_id: *extras_id,
+            })
+            .collect::<Vec<_>>();
+        let conn = MockDatabase::new(DbBackend::MySql)
+            .append_query_results([[slot]])
+            .append_query_results([[BTreeMap::from([("num_items", Value::Int(Some(1)))])]])
+            .append_query_results([dinners])
+            .append_query_results([extras])
+            .append_query_results([offered])
+            .append_query_results([Vec::<user::Model>::new()])
+            .into_connection();
+
+        validate_order(&conn, order, false).await
+    }
+
+    fn problems(result: Result<OrderContents, ServiceError>) -> Vec<String> {
+        match result {
+            Err(ServiceError::BadRequest(msg)) => msg.split("; ").map(str::to_string).collect(),
+            Err(err) => panic!("order was rejected with {:?}", err),
+            Ok(_) => panic!("order passed validation"),
+        }
+    }
+
+    fn next_week() -> NaiveDate {
+        Local::now().date_naive() + Duration::days(7)
+    }
+
+    #[actix_rt::test]
+    async fn two_portions_of_a_dinner_are_priced() {
+        let day = next_week();
+        let order = order(day, &[(1, &[2]), (1, &[])]);
+
+        let contents = validate(
+            &order,
+            slot(day, 1),
+            vec![dinner(1, day)],
+            vec![extra(2)],
+            &[(1, 2)],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(contents.price(&order), Decimal::new(3300, 2));
+    }
+
+    #[actix_rt::test]
+    async fn every_problem_is_reported_at_once() {
+        let day = next_week();
+        let mut order = order(day, &[(1, &[2]), (9, &[])]);
+        order.note = Some("x".repeat(NOTE_MAX_LEN + 1));
+
+        let problems = problems(
+            validate(
+                &order,
+                slot(day, 0),
+                vec![dinner(1, day)],
+                vec![extra(2)],
+                &[],
+            )
+            .await,
+        );
+
+        assert_eq!(
+            problems,
+            [
+                "Order note can't be longer than 200 characters",
+                "Pickup slot is no longer available",
+                "Extra 2 isn't served with Dinner 1",
+                "No dinner has id 9",
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn unknown_dinner_is_rejected() {
+        let day = next_week();
+        let order = order(day, &[(9, &[])]);
+
+        let problems = problems(validate(&order, slot(day, 1), vec![], vec![], &[]).await);
+
+        assert_eq!(problems, ["No dinner has id 9"]);
+    }
+
+    #[actix_rt::test]
+    async fn dinner_of_another_day_is_rejected() {
+        let day = next_week();
+        let order = order(day, &[(1, &[])]);
+        let monday_soup = dinner(1, day + Duration::days(1));
+
+        let problems =
+            problems(validate(&order, slot(day, 1), vec![monday_soup], vec![], &[]).await);
+
+        assert_eq!(problems, [format!("Dinner 1 is not served on {}", day)]);
+    }
+
+    #[actix_rt::test]
+    async fn inactive_slot_is_rejected() {
+        let day = next_week();
+        let order = order(day, &[(1, &[])]);
+
+        let problems =
+            problems(validate(&order, slot(day, 0), vec![dinner(1, day)], vec![], &[]).await);
+
+        assert_eq!(problems, ["Pickup slot is no longer available"]);
+    }
+
+    #[actix_rt::test]
+    async fn past_slot_is_rejected() {
+        let day = Local::now().date_naive() - Duration::days(1);
+        let order = order(day, &[(1, &[])]);
+
+        let problems =
+            problems(validate(&order, slot(day, 1), vec![dinner(1, day)], vec![], &[]).await);
+
+        assert_eq!(problems, ["Collection date is in the past"]);
+    }
+}
@@ -0,0 +1,888 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Local, Utc};
+use entity::{
+    model_enums::{AccountKind, TransactionKind},
+    payment_customer, user, wallet_account, wallet_entry, wallet_transaction,
+};
+use log::{error, info};
+use nanoid::nanoid;
+use sea_orm::{
+    prelude::Decimal, sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait,
+    DatabaseConnection, EntityTrait, JoinType, QueryFilter, QueryOrder, QuerySelect, RelationTrait,
+    SelectTwo, Set, TransactionTrait,
+};
+
+use crate::{
+    convert_err_to_500, csv_field,
+    errors::ServiceError,
+    map_db_err,
+    payments::PaymentProvider,
+    routes::structs::{CardRefund, CardRefundPart, TransactionFilter, WalletTransactionResponse},
+    stock::day_bounds,
+};
+
+//Wallets are a double-entry ledger, every movement of money is a transaction whose entries
+//add up to 0. User accounts cache their balance and are locked while it changes, system
+//accounts are only ever summed up from their entries so orders don't queue on a single row.
+//Functions that move money have to run inside a db transaction, the lock is held until it ends
+
+//balance in grosze, users who never topped up have 0
+pub async fn balance<C>(conn: &C, user_id: i32) -> Result<i64, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let account = wallet_account::Entity::find()
+        .filter(wallet_account::Column::UserId.eq(user_id))
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    Ok(account.map(|a| a.balance).unwrap_or(0))
+}
+
+//Same as `balance` but the account stays locked until the surrounding transaction ends,
+//so no payment can change it in the meantime
+pub async fn locked_balance<C>(conn: &C, user_id: i32) -> Result<i64, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let account = wallet_account::Entity::find()
+        .filter(wallet_account::Column::UserId.eq(user_id))
+        .lock_exclusive()
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    Ok(account.map(|a| a.balance).unwrap_or(0))
+}
+
+//charges the wallet for an order, returns the balance left
+pub async fn pay<C>(conn: &C, user_id: i32, amount: i64, order_id: i32) -> Result<i64, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    transfer(
+        conn,
+        user_id,
+        -amount,
+        AccountKind::Sales,
+        booking(TransactionKind::OrderPayment, Some(order_id), None),
+        false,
+    )
+    .await
+}
+
+//gives money spent on an order back to the wallet, returns the new balance
+pub async fn refund<C>(
+    conn: &C,
+    user_id: i32,
+    amount: i64,
+    order_id: i32,
+) -> Result<i64, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    transfer(
+        conn,
+        user_id,
+        amount,
+        AccountKind::Sales,
+        booking(TransactionKind::OrderRefund, Some(order_id), None),
+        false,
+    )
+    .await
+}
+
+//Books money collected by the payment provider, None when the payment was already booked.
+//The unique index on external_ref stops concurrent deliveries of the same payment
+pub async fn top_up<C>(
+    conn: &C,
+    user_id: i32,
+    amount: i64,
+    payment_ref: &str,
+) -> Result<Option<i64>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    if is_booked(conn, payment_ref).await? {
+        return Ok(None);
+    }
+
+    let mut transaction = booking(TransactionKind::TopUp, None, Some(payment_ref.to_string()));
+    transaction.payment_ref = Set(Some(payment_ref.to_string()));
+    transfer(
+        conn,
+        user_id,
+        amount,
+        AccountKind::Provider,
+        transaction,
+        false,
+    )
+    .await
+    .map(Some)
+}
+
+//user whose wallet the payment topped up, None when it was never booked
+pub async fn top_up_owner<C>(conn: &C, payment_ref: &str) -> Result<Option<i32>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let account = wallet_account::Entity::find()
+        .inner_join(wallet_entry::Entity)
+        .join(
+            JoinType::InnerJoin,
+            wallet_entry::Relation::WalletTransaction.def(),
+        )
+        .filter(wallet_transaction::Column::Kind.eq(TransactionKind::TopUp as u8))
+        .filter(wallet_transaction::Column::PaymentRef.eq(payment_ref))
+        .filter(wallet_account::Column::UserId.is_not_null())
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    Ok(account.and_then(|a| a.user_id))
+}
+
+//Brings refunds of a top-up made outside the app (e.g. from the Stripe dashboard) into the
+//ledger. `refunded_total` is everything refunded from the payment so far, only the part not
+//booked yet is taken from the wallet, which may go below 0 as the money is already gone.
+//Returns the new balance or None when there was nothing to book
+pub async fn sync_provider_refunds<C>(
+    conn: &C,
+    payment_ref: &str,
+    charge_id: &str,
+    refunded_total: i64,
+) -> Result<Option<i64>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let Some(user_id) = top_up_owner(conn, payment_ref).await? else {
+        return Err(ServiceError::BadRequest(
+            "Refunded payment isn't booked in any wallet".into(),
+        ));
+    };
+    //refunds booked by a concurrent request are seen once the lock is ours
+    let account = lock_user_account(conn, user_id).await?;
+    let refunded = refunded_amount(conn, account.id, payment_ref).await?;
+    if refunded_total <= refunded {
+        return Ok(None);
+    }
+
+    let mut transaction = booking(
+        TransactionKind::ProviderRefund,
+        None,
+        Some(format!("{}:{}", charge_id, refunded_total)),
+    );
+    transaction.payment_ref = Set(Some(payment_ref.to_string()));
+    transfer(
+        conn,
+        user_id,
+        refunded - refunded_total,
+        AccountKind::Provider,
+        transaction,
+        true,
+    )
+    .await
+    .map(Some)
+}
+
+//Takes a disputed top-up out of the wallet, the provider holds the money until the dispute
+//is settled. None when the dispute was already booked
+pub async fn book_chargeback<C>(
+    conn: &C,
+    payment_ref: &str,
+    dispute_id: &str,
+    amount: i64,
+) -> Result<Option<i64>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    if is_booked(conn, dispute_id).await? {
+        return Ok(None);
+    }
+    let Some(user_id) = top_up_owner(conn, payment_ref).await? else {
+        return Err(ServiceError::BadRequest(
+            "Disputed payment isn't booked in any wallet".into(),
+        ));
+    };
+
+    let mut transaction = booking(
+        TransactionKind::Chargeback,
+        None,
+        Some(dispute_id.to_string()),
+    );
+    transaction.payment_ref = Set(Some(payment_ref.to_string()));
+    transfer(
+        conn,
+        user_id,
+        -amount,
+        AccountKind::Provider,
+        transaction,
+        true,
+    )
+    .await
+    .map(Some)
+}
+
+//external_ref of a card refund booked before the provider was asked for it, replaced with
+//the provider's refund id once it's sent. One left behind means the refund was interrupted
+//and has to be checked against the provider
+const PENDING_REFUND_PREFIX: &str = "pending-refund:";
+//refunds still pending after this long aren't being sent anymore and get reconciled
+const PENDING_REFUND_TIMEOUT_MINUTES: i64 = 30;
+
+//card refund booked in the wallet but not sent yet
+struct PendingRefund {
+    external_ref: String,
+    payment_ref: String,
+    amount: i64,
+}
+
+//Sends unspent balance back to the cards it was paid with, newest top-ups first. `amount`
+//defaults to the whole balance, money that didn't come from a card (imports, adjustments)
+//stays in the wallet. Refunds are booked as pending before the provider is called, so a
+//charge.refunded webhook arriving meanwhile finds them booked, and the account isn't locked
+//during the calls. Parts the provider refused go back to the wallet, ones sent before stay
+//and are reported even when their bookings couldn't be updated
+pub async fn refund_to_card(
+    conn: &DatabaseConnection,
+    provider: &dyn PaymentProvider,
+    user_id: i32,
+    amount: Option<i64>,
+    admin_id: Option<i32>,
+) -> Result<CardRefund, ServiceError> {
+    let pending = book_pending_refunds(conn, user_id, amount, admin_id).await?;
+
+    let mut refunds = Vec::new();
+    let mut failed = None;
+    let mut unsent = Vec::new();
+    for part in pending {
+        if failed.is_some() {
+            unsent.push(part);
+            continue;
+        }
+        match provider
+            .refund(&part.payment_ref, part.amount, &part.external_ref)
+            .await
+        {
+            Ok(refund_id) => {
+                //the money is on its way back already, its booking just stays pending
+                if let Err(err) = confirm_refund(conn, &part, &refund_id).await {
+                    error!("Confirming refund {} failed: {}", refund_id, err);
+                }
+                refunds.push(CardRefundPart {
+                    payment_intent_id: part.payment_ref,
+                    refund_id,
+                    amount: part.amount,
+                });
+            }
+            Err(err) => {
+                error!("Refunding payment {} failed: {}", part.payment_ref, err);
+                failed = Some(err);
+                unsent.push(part);
+            }
+        }
+    }
+    if !unsent.is_empty() {
+        if let Err(err) = cancel_refunds(conn, user_id, &unsent).await {
+            error!(
+                "Giving unsent refunds of user {} back failed: {}",
+                user_id, err
+            );
+            failed = failed.or(Some(err));
+        }
+    }
+    if let Some(err) = failed.filter(|_| refunds.is_empty()) {
+        return Err(err);
+    }
+
+    Ok(CardRefund {
+        refunded: refunds.iter().map(|r| r.amount).sum(),
+        balance: balance(conn, user_id).await?,
+        refunds,
+    })
+}
+
+//takes the refunded amount out of the wallet at once, split among the top-ups it goes back to
+async fn book_pending_refunds(
+    conn: &DatabaseConnection,
+    user_id: i32,
+    amount: Option<i64>,
+    admin_id: Option<i32>,
+) -> Result<Vec<PendingRefund>, ServiceError> {
+    let txn = conn.begin().await.map_err(map_db_err)?;
+    let account = lock_user_account(&txn, user_id).await?;
+    let amount = amount.unwrap_or(account.balance);
+    if amount <= 0 {
+        return Err(ServiceError::BadRequest("Nothing to refund".into()));
+    }
+    if amount > account.balance {
+        return Err(ServiceError::BadRequest(
+            "Not enough money in wallet".into(),
+        ));
+    }
+
+    let mut left = amount;
+    let mut pending = Vec::new();
+    for (payment_ref, refundable) in refundable_payments(&txn, account.id).await? {
+        if left == 0 {
+            break;
+        }
+        let part = refundable.min(left);
+        let external_ref = format!("{}{}", PENDING_REFUND_PREFIX, nanoid!());
+        let mut transaction = booking(
+            TransactionKind::ProviderRefund,
+            None,
+            Some(external_ref.clone()),
+        );
+        transaction.payment_ref = Set(Some(payment_ref.clone()));
+        transaction.created_by = Set(admin_id);
+        transfer(
+            &txn,
+            user_id,
+            -part,
+            AccountKind::Provider,
+            transaction,
+            false,
+        )
+        .await?;
+
+        left -= part;
+        pending.push(PendingRefund {
+            external_ref,
+            payment_ref,
+            amount: part,
+        });
+    }
+    if pending.is_empty() {
+        return Err(ServiceError::BadRequest(
+            "No card payments left to refund".into(),
+        ));
+    }
+    txn.commit().await.map_err(map_db_err)?;
+
+    Ok(pending)
+}
+
+//Settles card refunds left pending by a request that died or couldn't update its bookings.
+//The provider is asked for each of them, ones it made are confirmed and the rest go back to
+//the wallet. A refund that can't be checked now stays pending for the next run
+pub async fn reconcile_pending_refunds(
+    conn: &DatabaseConnection,
+    provider: &dyn PaymentProvider,
+) -> Result<(), ServiceError> {
+    let stale = Utc::now() - chrono::Duration::minutes(PENDING_REFUND_TIMEOUT_MINUTES);
+    //user's side of the booking, its entry holds the amount taken from the wallet
+    let pending: Vec<(String, Option<String>, Option<i32>, i64)> = wallet_entry::Entity::find()
+        .select_only()
+        .column(wallet_transaction::Column::ExternalRef)
+        .column(wallet_transaction::Column::PaymentRef)
+        .column(wallet_account::Column::UserId)
+        .column(wallet_entry::Column::Amount)
+        .join(
+            JoinType::InnerJoin,
+            wallet_entry::Relation::WalletTransaction.def(),
+        )
+        .join(
+            JoinType::InnerJoin,
+            wallet_entry::Relation::WalletAccount.def(),
+        )
+        .filter(wallet_account::Column::UserId.is_not_null())
+        .filter(wallet_transaction::Column::Kind.eq(TransactionKind::ProviderRefund as u8))
+        .filter(
+            wallet_transaction::Column::ExternalRef.like(&format!("{}%", PENDING_REFUND_PREFIX)),
+        )
+        //cancelled and unsent bookings carry a suffix after the id
+        .filter(
+            wallet_transaction::Column::ExternalRef
+                .not_like(&format!("{}%:%", PENDING_REFUND_PREFIX)),
+        )
+        .filter(wallet_transaction::Column::CreatedAt.lt(stale))
+        .into_tuple()
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+
+    for (external_ref, payment_ref, user_id, amount) in pending {
+        let (Some(payment_ref), Some(user_id)) = (payment_ref, user_id) else {
+            continue;
+        };
+        let refund = PendingRefund {
+            external_ref,
+            payment_ref,
+            amount: -amount,
+        };
+        let settled = match provider
+            .find_refund(&refund.payment_ref, &refund.external_ref)
+            .await
+        {
+            Ok(Some(refund_id)) => {
+                info!(
+                    "Confirming pending refund {} as {}",
+                    refund.external_ref, refund_id
+                );
+                confirm_refund(conn, &refund, &refund_id).await
+            }
+            Ok(None) => {
+                info!("Giving never sent refund {} back", refund.external_ref);
+                cancel_refunds(conn, user_id, std::slice::from_ref(&refund)).await
+            }
+            Err(err) => Err(err),
+        };
+        if let Err(err) = settled {
+            error!("Reconciling refund {} failed: {}", refund.external_ref, err);
+        }
+    }
+
+    Ok(())
+}
+
+//removes the customer of a deleted wallet at the provider and marks it as removed there
+pub async fn delete_provider_customer(
+    conn: &DatabaseConnection,
+    provider: &dyn PaymentProvider,
+    customer_id: &str,
+) -> Result<(), ServiceError> {
+    provider.delete_customer(customer_id).await?;
+    payment_customer::Entity::update_many()
+        .col_expr(
+            payment_customer::Column::ProviderDeletedAt,
+            Expr::value(Utc::now()),
+        )
+        .filter(payment_customer::Column::CustomerId.eq(customer_id))
+        .exec(conn)
+        .await
+        .map_err(map_db_err)?;
+    Ok(())
+}
+
+//customers of deleted wallets the provider failed to delete, every one is tried again
+pub async fn retry_customer_deletions(
+    conn: &DatabaseConnection,
+    provider: &dyn PaymentProvider,
+) -> Result<(), ServiceError> {
+    let customers = payment_customer::Entity::find()
+        .filter(payment_customer::Column::DeletedAt.is_not_null())
+        .filter(payment_customer::Column::ProviderDeletedAt.is_null())
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+
+    for customer in customers {
+        if let Err(err) = delete_provider_customer(conn, provider, &customer.customer_id).await {
+            error!("Deleting customer {} failed: {}", customer.customer_id, err);
+        }
+    }
+    Ok(())
+}
+
+//the refund was sent, its booking now points at the provider's refund
+async fn confirm_refund(
+    conn: &DatabaseConnection,
+    refund: &PendingRefund,
+    refund_id: &str,
+) -> Result<(), ServiceError> {
+    wallet_transaction::Entity::update_many()
+        .col_expr(
+            wallet_transaction::Column::ExternalRef,
+            Expr::value(refund_id),
+        )
+        .filter(wallet_transaction::Column::ExternalRef.eq(refund.external_ref.as_str()))
+        .exec(conn)
+        .await
+        .map_err(map_db_err)?;
+    Ok(())
+}
+
+//Gives refunds that were never sent back to the wallet, their pending bookings stay as history
+//marked as unsent. One that was already confirmed or given back meanwhile is skipped
+async fn cancel_refunds(
+    conn: &DatabaseConnection,
+    user_id: i32,
+    refunds: &[PendingRefund],
+) -> Result<(), ServiceError> {
+    let txn = conn.begin().await.map_err(map_db_err)?;
+    for refund in refunds {
+        let marked = wallet_transaction::Entity::update_many()
+            .col_expr(
+                wallet_transaction::Column::ExternalRef,
+                Expr::value(format!("{}:unsent", refund.external_ref)),
+            )
+            .filter(wallet_transaction::Column::ExternalRef.eq(refund.external_ref.as_str()))
+            .exec(&txn)
+            .await
+            .map_err(map_db_err)?;
+        if marked.rows_affected == 0 {
+            continue;
+        }
+
+        let mut transaction = booking(
+            TransactionKind::ProviderRefund,
+            None,
+            Some(format!("{}:cancelled", refund.external_ref)),
+        );
+        transaction.payment_ref = Set(Some(refund.payment_ref.clone()));
+        transfer(
+            &txn,
+            user_id,
+            refund.amount,
+            AccountKind::Provider,
+            transaction,
+            false,
+        )
+        .await?;
+    }
+    txn.commit().await.map_err(map_db_err)
+}
+
+//top-ups of the account newest first with what's left of them to refund,
+//disputed ones are left out as the provider already holds their money
+async fn refundable_payments<C>(
+    conn: &C,
+    account_id: i32,
+) -> Result<Vec<(String, i64)>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let entries = wallet_entry::Entity::find()
+        .find_also_related(wallet_transaction::Entity)
+        .filter(wallet_entry::Column::AccountId.eq(account_id))
+        .filter(wallet_transaction::Column::PaymentRef.is_not_null())
+        .order_by_desc(wallet_entry::Column::Id)
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+
+    let mut top_ups = Vec::new();
+    let mut refunded: HashMap<String, i64> = HashMap::new();
+    let mut disputed = HashSet::new();
+    for (entry, transaction) in entries {
+        let Some(transaction) = transaction else {
+            continue;
+        };
+        let Some(payment_ref) = transaction.payment_ref else {
+            continue;
+        };
+        match TransactionKind::from_repr(transaction.kind) {
+            Some(TransactionKind::TopUp) => top_ups.push((payment_ref, entry.amount)),
+            Some(TransactionKind::ProviderRefund) => {
+                *refunded.entry(payment_ref).or_default() -= entry.amount
+            }
+            Some(TransactionKind::Chargeback) => {
+                disputed.insert(payment_ref);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(top_ups
+        .into_iter()
+        .filter(|(payment_ref, _)| !disputed.contains(payment_ref))
+        .map(|(payment_ref, amount)| {
+            let left = amount - refunded.get(&payment_ref).copied().unwrap_or_default();
+            (payment_ref, left)
+        })
+        .filter(|(_, left)| *left > 0)
+        .collect())
+}
+
+//grosze of given payment already given back to the card, as booked in the account
+async fn refunded_amount<C>(
+    conn: &C,
+    account_id: i32,
+    payment_ref: &str,
+) -> Result<i64, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let entries = wallet_entry::Entity::find()
+        .inner_join(wallet_transaction::Entity)
+        .filter(wallet_entry::Column::AccountId.eq(account_id))
+        .filter(wallet_transaction::Column::Kind.eq(TransactionKind::ProviderRefund as u8))
+        .filter(wallet_transaction::Column::PaymentRef.eq(payment_ref))
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+    Ok(-entries.iter().map(|e| e.amount).sum::<i64>())
+}
+
+//manual correction by an admin, `amount` is negative when taking money away
+pub async fn adjust<C>(
+    conn: &C,
+    user_id: i32,
+    amount: i64,
+    admin_id: i32,
+    note: String,
+) -> Result<i64, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let mut transaction = booking(TransactionKind::Adjustment, None, None);
+    transaction.note = Set(Some(note));
+    transaction.created_by = Set(Some(admin_id));
+    transfer(
+        conn,
+        user_id,
+        amount,
+        AccountKind::Adjustments,
+        transaction,
+        false,
+    )
+    .await
+}
+
+async fn is_booked<C>(conn: &C, external_ref: &str) -> Result<bool, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let found = wallet_transaction::Entity::find()
+        .filter(wallet_transaction::Column::ExternalRef.eq(external_ref))
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    Ok(found.is_some())
+}
+
+//user's side of every matching transaction newest first, None when the wallet was never used
+pub async fn history<C>(
+    conn: &C,
+    user_id: i32,
+    filter: &TransactionFilter,
+) -> Result<Option<SelectTwo<wallet_entry::Entity, wallet_transaction::Entity>>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let account = wallet_account::Entity::find()
+        .filter(wallet_account::Column::UserId.eq(user_id))
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    let Some(account) = account else {
+        return Ok(None);
+    };
+
+    let mut select = wallet_entry::Entity::find()
+        .find_also_related(wallet_transaction::Entity)
+        .filter(wallet_entry::Column::AccountId.eq(account.id));
+    if let Some(from) = filter.from {
+        select = select.filter(wallet_transaction::Column::CreatedAt.gte(day_bounds(from).0));
+    }
+    if let Some(to) = filter.to {
+        select = select.filter(wallet_transaction::Column::CreatedAt.lt(day_bounds(to).1));
+    }
+    if let Some(kinds) = &filter.kind {
+        select = select
+            .filter(wallet_transaction::Column::Kind.is_in(kinds.iter().map(|kind| *kind as u8)));
+    }
+
+    Ok(Some(select.order_by_desc(wallet_entry::Column::Id)))
+}
+
+pub fn transaction_response(
+    entry: wallet_entry::Model,
+    transaction: Option<wallet_transaction::Model>,
+) -> Result<WalletTransactionResponse, ServiceError> {
+    let transaction = transaction.ok_or(ServiceError::InternalError)?;
+    let kind = TransactionKind::from_repr(transaction.kind).ok_or(ServiceError::InternalError)?;
+
+    Ok(WalletTransactionResponse {
+        transaction_id: transaction.id,
+        kind,
+        amount: entry.amount,
+        balance: entry.balance_after.unwrap_or_default(),
+        order_id: transaction.order_id,
+        payment_intent_id: transaction.payment_ref,
+        note: transaction.note,
+        created_at: transaction.created_at,
+    })
+}
+
+//amounts in złoty for spreadsheets, times in local time
+pub fn transactions_csv(transactions: &[WalletTransactionResponse]) -> String {
+    let mut csv = String::from("date,kind,amount,balance,order_id,payment_intent_id,note\n");
+    for transaction in transactions {
+        csv.push_str(&format!(
+            "{},{:?},{},{},{},{},{}\n",
+            transaction
+                .created_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            transaction.kind,
+            Decimal::new(transaction.amount, 2),
+            Decimal::new(transaction.balance, 2),
+            transaction
+                .order_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            csv_field(transaction.payment_intent_id.as_deref().unwrap_or_default()),
+            csv_field(transaction.note.as_deref().unwrap_or_default())
+        ));
+    }
+    csv
+}
+
+fn booking(
+    kind: TransactionKind,
+    order_id: Option<i32>,
+    external_ref: Option<String>,
+) -> wallet_transaction::ActiveModel {
+    wallet_transaction::ActiveModel {
+        kind: Set(kind as u8),
+        order_id: Set(order_id),
+        external_ref: Set(external_ref),
+        created_at: Set(Utc::now()),
+        ..Default::default()
+    }
+}
+
+//Moves `amount` into the user's wallet (out of it when negative) from given system account.
+//`allow_negative` is only for money the provider already took back
+async fn transfer<C>(
+    conn: &C,
+    user_id: i32,
+    amount: i64,
+    counter_kind: AccountKind,
+    transaction: wallet_transaction::ActiveModel,
+    allow_negative: bool,
+) -> Result<i64, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let account = lock_user_account(conn, user_id).await?;
+    let balance = account.balance + amount;
+    if balance < 0 && !allow_negative {
+        return Err(ServiceError::BadRequest(
+            "Not enough money in wallet".into(),
+        ));
+    }
+    let counter = system_account(conn, counter_kind).await?;
+
+    let transaction = transaction.insert(conn).await.map_err(map_db_err)?;
+
+    wallet_entry::Entity::insert_many([
+        wallet_entry::ActiveModel {
+            transaction_id: Set(transaction.id),
+            account_id: Set(account.id),
+            amount: Set(amount),
+            balance_after: Set(Some(balance)),
+            ..Default::default()
+        },
+        wallet_entry::ActiveModel {
+            transaction_id: Set(transaction.id),
+            account_id: Set(counter.id),
+            amount: Set(-amount),
+            balance_after: Set(None),
+            ..Default::default()
+        },
+    ])
+    .exec(conn)
+    .await
+    .map_err(map_db_err)?;
+
+    let mut account: wallet_account::ActiveModel = account.into();
+    account.balance = Set(balance);
+    account.update(conn).await.map_err(map_db_err)?;
+
+    Ok(balance)
+}
+
+//user's account locked for update, opened on first use
+async fn lock_user_account<C>(conn: &C, user_id: i32) -> Result<wallet_account::Model, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    let find = || {
+        wallet_account::Entity::find()
+            .filter(wallet_account::Column::UserId.eq(user_id))
+            .lock_exclusive()
+            .one(conn)
+    };
+    if let Some(account) = find().await.map_err(map_db_err)? {
+        return Ok(account);
+    }
+
+    let opened = wallet_account::ActiveModel {
+        user_id: Set(Some(user_id)),
+        kind: Set(AccountKind::User as u8),
+        balance: Set(0),
+        created_at: Set(Utc::now()),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await;
+    match opened {
+        Ok(account) => Ok(account),
+        //opened by a concurrent request in the meantime
+        Err(err) => find()
+            .await
+            .map_err(map_db_err)?
+            .ok_or_else(|| map_db_err(err)),
+    }
+}
+
+async fn system_account<C>(
+    conn: &C,
+    kind: AccountKind,
+) -> Result<wallet_account::Model, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    wallet_account::Entity::find()
+        .filter(wallet_account::Column::UserId.is_null())
+        .filter(wallet_account::Column::Kind.eq(kind as u8))
+        .one(conn)
+        .await
+        .map_err(map_db_err)?
+        .ok_or_else(|| convert_err_to_500("no system wallet account", Some("Wallet err")))
+}
+
+//One-off move of balances kept on Stripe customers into the ledger, safe to run again
+//as every customer is booked once. Returns how many wallets were imported
+pub async fn import_provider_balances(
+    conn: &DatabaseConnection,
+    provider: &dyn PaymentProvider,
+) -> Result<usize, ServiceError> {
+    let users = user::Entity::find()
+        .filter(user::Column::StripeId.is_not_null())
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+
+    let mut imported = 0;
+    for user in users {
+        let Some(stripe_id) = user.stripe_id else {
+            continue;
+        };
+        let import_ref = format!("import:{}", stripe_id);
+        if is_booked(conn, &import_ref).await? {
+            continue;
+        }
+
+        let customer = match provider.customer(&stripe_id).await {
+            Ok(customer) => customer,
+            Err(err) => {
+                error!("Fetching customer of user {} failed: {}", user.id, err);
+                continue;
+            }
+        };
+        let amount = customer.balance;
+        if amount <= 0 {
+            continue;
+        }
+
+        let txn = conn.begin().await.map_err(map_db_err)?;
+        transfer(
+            &txn,
+            user.id,
+            amount,
+            AccountKind::Provider,
+            booking(TransactionKind::Import, None, Some(import_ref)),
+            false,
+        )
+        .await?;
+        txn.commit().await.map_err(map_db_err)?;
+
+        info!("Imported balance {} of user {}", amount, user.id);
+        imported += 1;
+    }
+
+    Ok(imported)
+}
@@ -0,0 +1,301 @@
+use chrono::{DateTime, Duration, Utc};
+use entity::{model_enums::WebhookStatus, payment_customer, user, webhook_event};
+use log::{info, warn};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    EntityTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+
+use crate::{
+    errors::ServiceError,
+    map_db_err,
+    payments::{PaymentProvider, WebhookEventKind},
+    send_mail,
+    wallet::{book_chargeback, sync_provider_refunds, top_up},
+};
+
+//Every delivery is stored under the provider's event id before it's handled, so retried
+//deliveries are answered without doing anything twice and failed ones can be replayed later
+
+//a delivery still marked as received after this long died halfway and may be taken over
+const RECEIVED_TIMEOUT_MINUTES: i64 = 10;
+const ERROR_MAX_LEN: usize = 255;
+
+fn stale(received_at: &DateTime<Utc>) -> bool {
+    *received_at < Utc::now() - Duration::minutes(RECEIVED_TIMEOUT_MINUTES)
+}
+
+fn in_progress() -> ServiceError {
+    ServiceError::Conflict("Webhook event is still being processed".into())
+}
+
+fn status_of(event: &webhook_event::Model) -> Result<WebhookStatus, ServiceError> {
+    WebhookStatus::from_repr(event.status).ok_or(ServiceError::InternalError)
+}
+
+//Handles a delivery from the payment provider once. Errors are returned so the provider
+//retries, anything the app doesn't act on is stored as ignored
+pub async fn receive(
+    conn: &DatabaseConnection,
+    provider: &dyn PaymentProvider,
+    payload: &str,
+    signature: &str,
+) -> Result<WebhookStatus, ServiceError> {
+    let event = provider.verify_webhook(payload, signature)?;
+
+    let stored = webhook_event::Entity::find()
+        .filter(webhook_event::Column::EventId.eq(event.id.as_str()))
+        .one(conn)
+        .await
+        .map_err(map_db_err)?;
+    let id = match stored {
+        Some(stored) => match status_of(&stored)? {
+            status @ (WebhookStatus::Processed | WebhookStatus::Ignored) => {
+                info!("Webhook event {} was already processed", stored.event_id);
+                return Ok(status);
+            }
+            WebhookStatus::Received if !stale(&stored.received_at) => return Err(in_progress()),
+            _ => {
+                claim(conn, &stored).await?;
+                stored.id
+            }
+        },
+        None => {
+            //unique event_id index lets only one of concurrent deliveries through
+            let inserted = webhook_event::ActiveModel {
+                event_id: Set(event.id.clone()),
+                event_type: Set(event.event_type.clone()),
+                payload: Set(payload.to_string()),
+                status: Set(WebhookStatus::Received as u8),
+                attempts: Set(1),
+                received_at: Set(Utc::now()),
+                ..Default::default()
+            }
+            .insert(conn)
+            .await;
+            match inserted {
+                Ok(inserted) => inserted.id,
+                Err(err) => {
+                    let taken = webhook_event::Entity::find()
+                        .filter(webhook_event::Column::EventId.eq(event.id.as_str()))
+                        .one(conn)
+                        .await
+                        .map_err(map_db_err)?;
+                    return Err(match taken {
+                        Some(_) => in_progress(),
+                        None => map_db_err(err),
+                    });
+                }
+            }
+        }
+    };
+
+    process(conn, id, event.kind).await
+}
+
+//Handles a stored event again, e.g. after fixing what made it fail.
+//Handlers are idempotent so replaying a processed event books nothing twice
+pub async fn replay(
+    conn: &DatabaseConnection,
+    provider: &dyn PaymentProvider,
+    id: i32,
+) -> Result<WebhookStatus, ServiceError> {
+    let stored = webhook_event::Entity::find_by_id(id)
+        .one(conn)
+        .await
+        .map_err(map_db_err)?
+        .ok_or_else(|| ServiceError::NotFound("No webhook event has given id".into()))?;
+    if status_of(&stored)? == WebhookStatus::Received && !stale(&stored.received_at) {
+        return Err(in_progress());
+    }
+
+    let event = provider.parse_webhook(&stored.payload)?;
+    claim(conn, &stored).await?;
+    process(conn, stored.id, event.kind).await
+}
+
+//replays every failed event oldest first, returns how many of them went through
+pub async fn replay_failed(
+    conn: &DatabaseConnection,
+    provider: &dyn PaymentProvider,
+) -> Result<usize, ServiceError> {
+    let failed = webhook_event::Entity::find()
+        .filter(webhook_event::Column::Status.eq(WebhookStatus::Failed as u8))
+        .order_by_asc(webhook_event::Column::Id)
+        .all(conn)
+        .await
+        .map_err(map_db_err)?;
+
+    let mut replayed = 0;
+    for event in failed {
+        match replay(conn, provider, event.id).await {
+            Ok(_) => replayed += 1,
+            Err(err) => warn!("Replaying webhook event {} failed: {}", event.event_id, err),
+        }
+    }
+    Ok(replayed)
+}
+
+//marks a stored event as being processed again, fails when someone else got to it first
+async fn claim(
+    conn: &DatabaseConnection,
+    stored: &webhook_event::Model,
+) -> Result<(), ServiceError> {
+    let claimed = webhook_event::Entity::update_many()
+        .col_expr(
+            webhook_event::Column::Status,
+            Expr::value(WebhookStatus::Received as u8),
+        )
+        .col_expr(
+            webhook_event::Column::Attempts,
+            Expr::col(webhook_event::Column::Attempts).add(1),
+        )
+        .col_expr(webhook_event::Column::ReceivedAt, Expr::value(Utc::now()))
+        .filter(webhook_event::Column::Id.eq(stored.id))
+        .filter(webhook_event::Column::Attempts.eq(stored.attempts))
+        .exec(conn)
+        .await
+        .map_err(map_db_err)?;
+    if claimed.rows_affected == 0 {
+        return Err(in_progress());
+    }
+    Ok(())
+}
+
+async fn process(
+    conn: &DatabaseConnection,
+    id: i32,
+    kind: WebhookEventKind,
+) -> Result<WebhookStatus, ServiceError> {
+    let result = handle(conn, kind).await;
+    let (status, error) = match &result {
+        Ok(status) => (*status, None),
+        Err(err) => (
+            WebhookStatus::Failed,
+            Some(err.to_string().chars().take(ERROR_MAX_LEN).collect()),
+        ),
+    };
+
+    webhook_event::ActiveModel {
+        id: Set(id),
+        status: Set(status as u8),
+        error: Set(error),
+        processed_at: Set(Some(Utc::now())),
+        ..Default::default()
+    }
+    .update(conn)
+    .await
+    .map_err(map_db_err)?;
+
+    result
+}
+
+async fn handle(
+    conn: &DatabaseConnection,
+    kind: WebhookEventKind,
+) -> Result<WebhookStatus, ServiceError> {
+    match kind {
+        WebhookEventKind::PaymentSucceeded {
+            intent_id,
+            customer_id,
+            amount,
+        } => {
+            let user = match customer_id {
+                Some(customer_id) => customer_user(conn, &customer_id).await?,
+                None => None,
+            };
+            let Some(user) = user else {
+                return Err(ServiceError::BadRequest(
+                    "Paid intent doesn't belong to any user".into(),
+                ));
+            };
+
+            //Stripe only collects the money, the wallet itself lives in the ledger
+            let txn = conn.begin().await.map_err(map_db_err)?;
+            let booked = top_up(&txn, user.id, amount, &intent_id).await?;
+            txn.commit().await.map_err(map_db_err)?;
+            if booked.is_none() {
+                info!("Payment intent {} was already booked", intent_id);
+            }
+        }
+        WebhookEventKind::PaymentFailed {
+            intent_id,
+            customer_id,
+            reason,
+        } => {
+            let user = match customer_id {
+                Some(customer_id) => customer_user(conn, &customer_id).await?,
+                None => None,
+            };
+            let Some(user) = user else {
+                return Ok(WebhookStatus::Ignored);
+            };
+
+            info!("Payment intent {} of user {} failed", intent_id, user.id);
+            send_mail(
+                &user.email,
+                "Kantyna - nieudane doładowanie",
+                format!(
+                    "Doładowanie portfela nie powiodło się, środki nie zostały pobrane.\nPowód: {}",
+                    reason.as_deref().unwrap_or("nieznany")
+                ),
+            )?;
+        }
+        WebhookEventKind::ChargeRefunded {
+            charge_id,
+            intent_id,
+            amount_refunded,
+        } => {
+            let Some(intent_id) = intent_id else {
+                return Ok(WebhookStatus::Ignored);
+            };
+
+            let txn = conn.begin().await.map_err(map_db_err)?;
+            let booked =
+                sync_provider_refunds(&txn, &intent_id, &charge_id, amount_refunded).await?;
+            txn.commit().await.map_err(map_db_err)?;
+            if let Some(balance) = booked {
+                info!(
+                    "Refund of payment intent {} booked, wallet balance {}",
+                    intent_id, balance
+                );
+            }
+        }
+        WebhookEventKind::DisputeCreated {
+            dispute_id,
+            intent_id,
+            amount,
+            reason,
+        } => {
+            let Some(intent_id) = intent_id else {
+                return Ok(WebhookStatus::Ignored);
+            };
+
+            let txn = conn.begin().await.map_err(map_db_err)?;
+            let booked = book_chargeback(&txn, &intent_id, &dispute_id, amount).await?;
+            txn.commit().await.map_err(map_db_err)?;
+            if let Some(balance) = booked {
+                warn!(
+                    "Payment intent {} disputed ({}), {} taken from the wallet, balance {}",
+                    intent_id, reason, amount, balance
+                );
+            }
+        }
+        WebhookEventKind::Other => return Ok(WebhookStatus::Ignored),
+    }
+
+    Ok(WebhookStatus::Processed)
+}
+
+//deleted customers count too, their payments may still be delivered after the wallet is gone
+async fn customer_user<C>(conn: &C, customer_id: &str) -> Result<Option<user::Model>, ServiceError>
+where
+    C: ConnectionTrait,
+{
+    user::Entity::find()
+        .inner_join(payment_customer::Entity)
+        .filter(payment_customer::Column::CustomerId.eq(customer_id))
+        .one(conn)
+        .await
+        .map_err(map_db_err)
+}
@@ -0,0 +1,207 @@
+//Fixtures shared by the integration tests, the database is SeaORM's mock so every query
+//a test expects has to be scripted in the order the code runs it
+#![allow(dead_code)]
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use async_std::sync::RwLock;
+use chrono::{Duration, Local, NaiveDate, NaiveTime};
+use entity::{
+    dinner, dinner_stock, extras, extras_dinner, pickup_slot,
+    sea_orm_active_enums::{ExtrasType, Type},
+    user, wallet_account, wallet_transaction,
+};
+use kantyna_api::{
+    appstate::AppState,
+    events::EventBus,
+    payments::FakeProvider,
+    routes::structs::{Dinner, OrderRequest},
+};
+use rust_decimal::Decimal;
+use sea_orm::{DatabaseConnection, MockDatabase, Transaction, Value};
+
+pub const USER_ID: i32 = 1;
+pub const SLOT_ID: i32 = 3;
+pub const DINNER_ID: i32 = 5;
+pub const EXTRA_ID: i32 = 7;
+pub const WEBHOOK_SECRET: &str = "whsec_integration_test";
+
+pub fn app_state(conn: DatabaseConnection, payments: Arc<FakeProvider>) -> AppState {
+    //pickup codes are signed into QR payloads
+    std::env::set_var("JWT_SECRET", "integration-test-secret");
+    AppState {
+        conn,
+        activators_del: Arc::new(RwLock::new(Default::default())),
+        activators_reg: Arc::new(RwLock::new(Default::default())),
+        payments,
+        events: EventBus::new(16),
+    }
+}
+
+//a week ahead so the slot is never in the past
+pub fn collection_day() -> NaiveDate {
+    Local::now().date_naive() + Duration::days(7)
+}
+
+fn week_day() -> u8 {
+    use chrono::Datelike;
+    collection_day().weekday().num_days_from_monday() as u8
+}
+
+pub fn customer() -> user::Model {
+    user::Model {
+        id: USER_ID,
+        email: "jan@example.com".into(),
+        username: "jan".into(),
+        password: String::new(),
+        verified: 1,
+        admin: 0,
+        stripe_id: Some("cus_fake_1".into()),
+        no_shows: 0,
+        blocked_until: None,
+    }
+}
+
+pub fn slot() -> pickup_slot::Model {
+    pickup_slot(week_day())
+}
+
+pub fn pickup_slot(week_day: u8) -> pickup_slot::Model {
+    pickup_slot::Model {
+        id: SLOT_ID,
+        week_day,
+        start_time: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+        end_time: NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+        capacity: 20,
+        active: 1,
+    }
+}
+
+pub fn dinner() -> dinner::Model {
+    dinner::Model {
+        id: DINNER_ID,
+        name: "Pierogi".into(),
+        price: Decimal::new(1500, 2),
+        image: String::new(),
+        week_day: week_day(),
+        max_supply: 50,
+        r#type: Type::Main,
+    }
+}
+
+pub fn extra() -> extras::Model {
+    extras::Model {
+        id: EXTRA_ID,
+        name: "Kompot".into(),
+        price: Decimal::new(300, 2),
+        image: String::new(),
+        r#type: ExtrasType::Beverage,
+    }
+}
+
+pub fn stock() -> (dinner_stock::Model, dinner::Model) {
+    let stock = dinner_stock::Model {
+        id: 1,
+        dinner_id: DINNER_ID,
+        day: collection_day(),
+        reserved: 1,
+    };
+    (stock, dinner())
+}
+
+pub fn account(user_id: Option<i32>, kind: u8, balance: i64) -> wallet_account::Model {
+    wallet_account::Model {
+        id: user_id.unwrap_or(100 + kind as i32),
+        user_id,
+        kind,
+        balance,
+        created_at: chrono::Utc::now(),
+    }
+}
+
+pub fn booked(id: i32, kind: u8, payment_ref: Option<&str>) -> wallet_transaction::Model {
+    wallet_transaction::Model {
+        id,
+        kind,
+        order_id: None,
+        external_ref: payment_ref.map(str::to_string),
+        created_at: chrono::Utc::now(),
+        note: None,
+        created_by: None,
+        payment_ref: payment_ref.map(str::to_string),
+    }
+}
+
+//row returned by PaginatorTrait::count
+pub fn count(n: i32) -> BTreeMap<&'static str, Value> {
+    BTreeMap::from([("num_items", Value::Int(Some(n)))])
+}
+
+pub fn order_request() -> OrderRequest {
+    OrderRequest {
+        dinners: vec![Dinner {
+            dinner_id: DINNER_ID,
+            extras_ids: vec![EXTRA_ID],
+            note: None,
+            dietary_flags: Vec::new(),
+            attendee_user_id: None,
+            attendee_name: None,
+        }],
+        collection_day: collection_day(),
+        slot_id: SLOT_ID,
+        note: None,
+    }
+}
+
+//Everything `place_order` reads before it starts writing: the customer, the menu of the day,
+//the locked slot with its usage and the reserved stock
+pub fn order_reads(db: MockDatabase) -> MockDatabase {
+    db.append_query_results([[customer()]])
+        .append_query_results([[slot()]])
+        .append_query_results([[count(1)]])
+        .append_query_results([[dinner()]])
+        .append_query_results([[extra()]])
+        .append_query_results([[extras_dinner::Model {
+            id: 1,
+            dinner_id: DINNER_ID,
+            extras_id: EXTRA_ID,
+        }]])
+        .append_query_results([Vec::<user::Model>::new()])
+        .append_query_results([[slot()]])
+        .append_query_results([Vec::<pickup_slot::Model>::new()])
+        .append_query_results([[stock()]])
+        //pickup code is free in orders and in lines
+        .append_query_results([[count(0)], [count(0)]])
+}
+
+//every statement sent to the database, one debug line each with its values
+pub fn statements(log: &[Transaction]) -> Vec<String> {
+    format!("{:?}", log)
+        .split("Statement { ")
+        .skip(1)
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn ran(log: &[Transaction], sql: &str) -> Vec<String> {
+    statements(log)
+        .into_iter()
+        .filter(|statement| statement.contains(sql))
+        .collect()
+}
+
+//Nothing may be persisted: the statement that failed ran inside a transaction that was
+//rolled back right after it, and no transaction was ever committed
+pub fn assert_rolled_back_after(log: &[Transaction], failed_sql: &str) {
+    let statements = statements(log);
+    assert!(ran(log, "\"COMMIT\"").is_empty(), "{:#?}", statements);
+    let failed = statements
+        .iter()
+        .rposition(|statement| statement.contains(failed_sql))
+        .expect("failing statement never ran");
+    assert!(
+        statements[failed + 1].contains("\"ROLLBACK\""),
+        "{:#?}",
+        statements
+    );
+}
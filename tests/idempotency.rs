@@ -0,0 +1,79 @@
+mod common;
+
+use actix_web::{test::TestRequest, FromRequest};
+use chrono::{Duration, Utc};
+use common::*;
+use entity::idempotency_key;
+use kantyna_api::{
+    errors::ServiceError,
+    idempotency::{Idempotency, IDEMPOTENCY_HEADER},
+};
+use sea_orm::{DbBackend, MockDatabase};
+use sha2::{Digest, Sha256};
+
+const KEY: &str = "retry-me";
+const PATH: &str = "/api/payment/add-balance/2000";
+
+async fn idempotency() -> Idempotency {
+    let (req, mut payload) = TestRequest::post()
+        .uri(PATH)
+        .insert_header((IDEMPOTENCY_HEADER, KEY))
+        .to_http_parts();
+    Idempotency::from_request(&req, &mut payload).await.unwrap()
+}
+
+//claim of the first request, which never stored its response
+fn claim(age: Duration, request_hash: String) -> idempotency_key::Model {
+    idempotency_key::Model {
+        id: 1,
+        user_id: USER_ID,
+        key: KEY.into(),
+        request_hash,
+        status_code: None,
+        response_body: None,
+        created_at: Utc::now() - age,
+    }
+}
+
+//same request as the first one: its path and no body
+fn request_hash() -> String {
+    format!("{:x}", Sha256::digest(format!("{}\n", PATH)))
+}
+
+#[actix_rt::test]
+async fn retry_waits_for_a_running_request() {
+    let conn = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([[claim(Duration::minutes(1), request_hash())]])
+        .into_connection();
+
+    let response = idempotency()
+        .await
+        .respond(&conn, USER_ID, async { Ok("charged twice") })
+        .await;
+
+    match response {
+        Err(ServiceError::Conflict(msg)) => assert!(msg.contains("still being processed")),
+        _ => panic!("retry wasn't told to wait"),
+    }
+}
+
+//the first request may have charged before it died, running it again could charge twice
+#[actix_rt::test]
+async fn retry_of_a_request_that_died_needs_a_new_key() {
+    let conn = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([[claim(Duration::minutes(30), request_hash())]])
+        .into_connection();
+
+    let response = idempotency()
+        .await
+        .respond(&conn, USER_ID, async { Ok("charged twice") })
+        .await;
+
+    match response {
+        Err(ServiceError::Conflict(msg)) => assert!(msg.contains("new key")),
+        _ => panic!("request that died was run again"),
+    }
+    let log = conn.into_transaction_log();
+    assert!(ran(&log, "DELETE FROM `idempotency_key`").is_empty());
+    assert!(ran(&log, "INSERT INTO `idempotency_key`").is_empty());
+}
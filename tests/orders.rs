@@ -0,0 +1,332 @@
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use common::*;
+use entity::{
+    dinner_orders, dinner_stock, extras, extras_dinner, extras_order, model_enums::Status, user,
+    user_dinner_orders,
+};
+use kantyna_api::{
+    errors::ServiceError,
+    jwt_auth::AuthUser,
+    payments::FakeProvider,
+    pickup::qr_payload,
+    routes::{
+        admin::redeem_pickup,
+        order::{cancel_paid_order, modify_paid_order, place_order},
+        structs::ModifyOrderRequest,
+    },
+    slots::slot_start,
+};
+use rust_decimal::Decimal;
+use sea_orm::{DbBackend, DbErr, MockDatabase, MockExecResult, RuntimeErr};
+
+fn inserted(id: u64) -> MockExecResult {
+    MockExecResult {
+        last_insert_id: id,
+        rows_affected: 1,
+    }
+}
+
+//extras of the line fail to insert after the order, its history and the line are written
+#[actix_rt::test]
+async fn failed_insert_rolls_back_the_whole_order() {
+    let conn = order_reads(MockDatabase::new(DbBackend::MySql))
+        .append_exec_results([
+            //stock upsert, order, status history, line
+            inserted(1),
+            inserted(10),
+            inserted(1),
+            inserted(20),
+        ])
+        .append_exec_errors([DbErr::Exec(RuntimeErr::Internal(
+            "extras_order is gone".into(),
+        ))])
+        .into_connection();
+    let data = app_state(conn, Arc::new(FakeProvider::new(WEBHOOK_SECRET)));
+
+    let result = place_order(&data, USER_ID, order_request(), false).await;
+
+    assert!(result.is_err());
+    assert_rolled_back_after(
+        &data.conn.into_transaction_log(),
+        "INSERT INTO `extras_order`",
+    );
+}
+
+//every row of the order is written but the wallet can't pay for it
+#[actix_rt::test]
+async fn failed_payment_rolls_back_the_whole_order() {
+    let conn = order_reads(MockDatabase::new(DbBackend::MySql))
+        .append_exec_results([
+            inserted(1),
+            inserted(10),
+            inserted(1),
+            inserted(20),
+            inserted(1),
+        ])
+        .append_query_results([[account(Some(USER_ID), 0, 100)]])
+        .into_connection();
+    let data = app_state(conn, Arc::new(FakeProvider::new(WEBHOOK_SECRET)));
+
+    let result = place_order(&data, USER_ID, order_request(), false).await;
+
+    assert!(matches!(result, Err(ServiceError::BadRequest(_))));
+    assert_rolled_back_after(&data.conn.into_transaction_log(), "FROM `wallet_account`");
+}
+
+#[actix_rt::test]
+async fn order_is_paid_from_the_wallet_and_committed() {
+    let conn = order_reads(MockDatabase::new(DbBackend::MySql))
+        .append_exec_results([
+            inserted(1),
+            inserted(10),
+            inserted(1),
+            inserted(20),
+            inserted(1),
+            //ledger transaction, both entries, user's account
+            inserted(30),
+            inserted(31),
+            inserted(1),
+        ])
+        .append_query_results([[account(Some(USER_ID), 0, 5000)], [account(None, 2, 0)]])
+        .append_query_results([[booked(30, 1, None)]])
+        .append_query_results([[account(Some(USER_ID), 0, 3200)]])
+        .into_connection();
+    let data = app_state(conn, Arc::new(FakeProvider::new(WEBHOOK_SECRET)));
+    let mut events = data.events.subscribe();
+
+    let created = match place_order(&data, USER_ID, order_request(), false).await {
+        Ok(created) => created,
+        Err(err) => panic!("order wasn't placed: {}", err),
+    };
+
+    //15.00 dinner with a 3.00 drink
+    assert_eq!(created.total, 1800);
+    assert_eq!(created.balance, 3200);
+    assert!(events.try_recv().is_ok());
+    let log = data.conn.into_transaction_log();
+    let statements = statements(&log);
+    assert!(
+        statements.last().unwrap().contains("\"COMMIT\""),
+        "{:#?}",
+        statements
+    );
+    let paid = ran(&log, "UPDATE `wallet_account`");
+    assert!(paid[0].contains("BigInt(Some(3200))"), "{:#?}", paid);
+}
+
+fn paid_order(collection_date: DateTime<Utc>, price: i64, group: bool) -> dinner_orders::Model {
+    dinner_orders::Model {
+        id: 10,
+        user_id: USER_ID,
+        collection_date,
+        status: Status::Paid as u8,
+        price: Decimal::new(price, 2),
+        cancelled_by: None,
+        cancelled_at: None,
+        cancel_reason: None,
+        pickup_code: Some("ORDER2".into()),
+        slot_id: Some(SLOT_ID),
+        note: None,
+        is_group: group as i8,
+    }
+}
+
+fn line(id: i32, dinner_id: i32, pickup_code: Option<&str>) -> user_dinner_orders::Model {
+    user_dinner_orders::Model {
+        id,
+        order_id: 10,
+        dinner_id,
+        note: None,
+        dietary_flags: 0,
+        attendee_user_id: None,
+        attendee_name: pickup_code.map(|_| format!("Guest {}", id)),
+        pickup_code: pickup_code.map(str::to_string),
+        collected_at: None,
+    }
+}
+
+//group order of two people collected today, nobody took their dish yet
+fn group_order() -> (dinner_orders::Model, Vec<user_dinner_orders::Model>) {
+    let order = paid_order(Utc::now(), 3000, true);
+    let lines = vec![
+        line(20, DINNER_ID, Some("LINE22")),
+        line(21, DINNER_ID, Some("LINE33")),
+    ];
+    (order, lines)
+}
+
+fn with_extra(
+    line: &user_dinner_orders::Model,
+) -> (user_dinner_orders::Model, extras_order::Model) {
+    let extra = extras_order::Model {
+        id: line.id,
+        user_dinner_id: line.id,
+        extras_id: EXTRA_ID,
+    };
+    (line.clone(), extra)
+}
+
+//one guest took their dish, the order is still paid but can't be refunded in full anymore
+#[actix_rt::test]
+async fn partly_collected_group_order_is_not_cancelled() {
+    let (order, lines) = group_order();
+    let mut collected = lines.clone();
+    collected[0].collected_at = Some(Utc::now());
+    let conn = MockDatabase::new(DbBackend::MySql)
+        //redeeming the first guest's line
+        .append_query_results([[order.clone()]])
+        .append_query_results([lines.iter().map(with_extra).collect::<Vec<_>>()])
+        .append_exec_results([inserted(0)])
+        .append_query_results([[collected[0].clone()]])
+        //cancelling the order
+        .append_query_results([[order.clone()]])
+        .append_query_results([collected])
+        .into_connection();
+    let data = app_state(conn, Arc::new(FakeProvider::new(WEBHOOK_SECRET)));
+    let admin = AuthUser {
+        id: 2,
+        username: "kasjer".into(),
+        email: "kasjer@example.com".into(),
+        is_admin: true,
+        is_verified: true,
+    };
+
+    let payload = qr_payload(order.id, "LINE22", &order.collection_date).unwrap();
+    let redeemed = match redeem_pickup(&data, admin.id, &payload).await {
+        Ok(redeemed) => redeemed,
+        Err(err) => panic!("line wasn't redeemed: {}", err),
+    };
+    assert_eq!(redeemed.dinners.len(), 1);
+
+    let result = cancel_paid_order(&data, order.id, &admin, true, None).await;
+
+    assert!(
+        matches!(result, Err(ServiceError::BadRequest(_))),
+        "{:?}",
+        result.err()
+    );
+    let log = data.conn.into_transaction_log();
+    assert_eq!(ran(&log, "\"COMMIT\"").len(), 1);
+    assert!(ran(&log, "wallet").is_empty());
+    assert!(ran(&log, "dinner_stock").is_empty());
+    assert!(statements(&log).last().unwrap().contains("\"ROLLBACK\""));
+}
+
+fn owner() -> AuthUser {
+    AuthUser {
+        id: USER_ID,
+        username: "jan".into(),
+        email: "jan@example.com".into(),
+        is_admin: false,
+        is_verified: true,
+    }
+}
+
+//ledger moving `after - before` grosze into the user's wallet
+fn refunded(db: MockDatabase, before: i64, after: i64) -> MockDatabase {
+    db.append_query_results([[account(Some(USER_ID), 0, before)], [account(None, 2, 0)]])
+        .append_query_results([[booked(30, 2, None)]])
+        .append_query_results([[account(Some(USER_ID), 0, after)]])
+        .append_exec_results([inserted(30), inserted(31), inserted(1)])
+}
+
+#[actix_rt::test]
+async fn cancelled_order_gives_its_portions_back() {
+    let order = paid_order(slot_start(collection_day(), &slot()), 1500, false);
+    let mut cancelled = order.clone();
+    cancelled.status = Status::Cancelled as u8;
+    let db = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([[order.clone()]])
+        .append_query_results([[line(20, DINNER_ID, None), line(21, DINNER_ID, None)]])
+        //stock, status, its history and the cancellation itself
+        .append_exec_results([inserted(0), inserted(0), inserted(1), inserted(0)])
+        .append_query_results([[cancelled.clone()], [cancelled]]);
+    let conn = refunded(db, 1000, 2500).into_connection();
+    let data = app_state(conn, Arc::new(FakeProvider::new(WEBHOOK_SECRET)));
+
+    let cancelled = cancel_paid_order(&data, order.id, &owner(), false, None)
+        .await
+        .unwrap();
+
+    assert_eq!(cancelled.refunded, 1500);
+    let log = data.conn.into_transaction_log();
+    let released = ran(&log, "UPDATE `dinner_stock`");
+    assert_eq!(released.len(), 1);
+    //both portions of the dinner at once
+    assert!(released[0].contains("`reserved` - ?"), "{}", released[0]);
+    assert!(
+        released[0].contains("Int(Some(2)), Int(Some(5))"),
+        "{}",
+        released[0]
+    );
+    assert!(statements(&log).last().unwrap().contains("\"COMMIT\""));
+}
+
+//Pierogi are swapped for another dinner of the same price, its portion goes back before
+//the new dinner is reserved
+#[actix_rt::test]
+async fn changed_order_moves_its_portion_to_the_new_dinner() {
+    let order = paid_order(slot_start(collection_day(), &slot()), 1500, false);
+    let mut other = dinner();
+    other.id = DINNER_ID + 1;
+    other.name = "Bigos".into();
+    let stock = dinner_stock::Model {
+        id: 2,
+        dinner_id: other.id,
+        day: collection_day(),
+        reserved: 1,
+    };
+    let conn = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([[order.clone()]])
+        .append_query_results([[slot()]])
+        .append_query_results([[count(1)]])
+        .append_query_results([[other.clone()]])
+        .append_query_results([Vec::<extras::Model>::new()])
+        .append_query_results([Vec::<extras_dinner::Model>::new()])
+        .append_query_results([Vec::<user::Model>::new()])
+        .append_query_results([[line(20, DINNER_ID, None)]])
+        .append_query_results([[(stock, other.clone())]])
+        .append_query_results([[order.clone()]])
+        .append_query_results([[account(Some(USER_ID), 0, 1000)]])
+        .append_exec_results([
+            //release, reserve, old extras, old line, new line, order
+            inserted(0),
+            inserted(0),
+            inserted(0),
+            inserted(0),
+            inserted(22),
+            inserted(0),
+        ])
+        .into_connection();
+    let data = app_state(conn, Arc::new(FakeProvider::new(WEBHOOK_SECRET)));
+    let mut request = order_request();
+    request.dinners[0].dinner_id = other.id;
+    request.dinners[0].extras_ids.clear();
+
+    let modified = modify_paid_order(
+        &data,
+        order.id,
+        USER_ID,
+        ModifyOrderRequest {
+            dinners: request.dinners,
+            note: None,
+        },
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(modified.difference, 0);
+    let log = data.conn.into_transaction_log();
+    let statements = statements(&log);
+    let position = |sql: &str| statements.iter().position(|s| s.contains(sql)).unwrap();
+    let released = position("UPDATE `dinner_stock`");
+    let reserved = position("INSERT INTO `dinner_stock`");
+    assert!(released < reserved, "{:#?}", statements);
+    assert!(statements[released].contains("Int(Some(1)), Int(Some(5))"));
+    assert!(statements[reserved].contains("Int(Some(6))"));
+    assert!(statements.last().unwrap().contains("\"COMMIT\""));
+}
@@ -0,0 +1,322 @@
+mod common;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use chrono::Utc;
+use common::*;
+use entity::{model_enums::WebhookStatus, payment_customer, wallet_entry, webhook_event};
+use kantyna_api::{
+    errors::ServiceError,
+    payments::{FakeProvider, NewCustomer, PaymentProvider},
+    routes::structs::Address,
+    wallet::{reconcile_pending_refunds, refund_to_card, retry_customer_deletions},
+    webhooks::receive,
+};
+use sea_orm::{DbBackend, DbErr, MockDatabase, MockExecResult, RuntimeErr, Value};
+
+fn inserted(id: u64) -> MockExecResult {
+    MockExecResult {
+        last_insert_id: id,
+        rows_affected: 1,
+    }
+}
+
+fn stored(status: WebhookStatus, payload: &str) -> webhook_event::Model {
+    webhook_event::Model {
+        id: 1,
+        event_id: "evt_fake_3".into(),
+        event_type: "payment_intent.succeeded".into(),
+        payload: payload.into(),
+        status: status as u8,
+        attempts: 1,
+        error: None,
+        received_at: Utc::now(),
+        processed_at: None,
+    }
+}
+
+//customer with an intent of 20.00 waiting for the client to pay it
+async fn intent(provider: &FakeProvider) -> String {
+    let address = Address {
+        city: "Kraków".into(),
+        country: "PL".into(),
+        postal_code: "30-001".into(),
+        state: "małopolskie".into(),
+    };
+    let customer_id = provider
+        .create_customer(NewCustomer {
+            email: "jan@example.com",
+            name: "jan",
+            phone: "123456789",
+            address: &address,
+        })
+        .await
+        .unwrap();
+    assert_eq!(Some(customer_id.as_str()), customer().stripe_id.as_deref());
+    let secret = provider.create_intent(&customer_id, 2000).await.unwrap();
+    secret.trim_end_matches("_secret").to_string()
+}
+
+#[actix_rt::test]
+async fn paid_intent_tops_the_wallet_up() {
+    let provider = FakeProvider::new(WEBHOOK_SECRET);
+    let intent_id = intent(&provider).await;
+    let (payload, signature) = provider.intent_succeeded(&intent_id);
+    let conn = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([Vec::<webhook_event::Model>::new()])
+        .append_query_results([[stored(WebhookStatus::Received, &payload)]])
+        .append_query_results([[customer()]])
+        //not booked yet, user's and provider's accounts
+        .append_query_results([Vec::<entity::wallet_transaction::Model>::new()])
+        .append_query_results([[account(Some(USER_ID), 0, 0)], [account(None, 1, 0)]])
+        .append_query_results([[booked(30, 0, Some(&intent_id))]])
+        .append_query_results([[account(Some(USER_ID), 0, 2000)]])
+        .append_query_results([[stored(WebhookStatus::Processed, &payload)]])
+        .append_exec_results([
+            //stored event, ledger transaction, both entries, user's account, event processed
+            inserted(1),
+            inserted(30),
+            inserted(31),
+            inserted(1),
+            inserted(1),
+        ])
+        .into_connection();
+    let data = app_state(conn, Arc::new(provider));
+
+    let status = receive(&data.conn, data.payments.as_ref(), &payload, &signature).await;
+
+    assert!(matches!(status, Ok(WebhookStatus::Processed)));
+    let log = data.conn.into_transaction_log();
+    assert_eq!(ran(&log, "\"COMMIT\"").len(), 1);
+    //customers of deleted wallets still lead to their user
+    assert_eq!(ran(&log, "INNER JOIN `payment_customer`").len(), 1);
+    let top_up = ran(&log, "INSERT INTO `wallet_transaction`");
+    assert!(top_up[0].contains(&intent_id), "{:#?}", top_up);
+    let balance = ran(&log, "UPDATE `wallet_account`");
+    assert!(balance[0].contains("BigInt(Some(2000))"), "{:#?}", balance);
+}
+
+//without the secret nobody can make the app book money it never got
+#[actix_rt::test]
+async fn forged_webhook_is_rejected() {
+    let provider = FakeProvider::new(WEBHOOK_SECRET);
+    let intent_id = intent(&provider).await;
+    let (payload, _) = provider.intent_succeeded(&intent_id);
+    let forged = [
+        FakeProvider::new("whsec_guessed").sign(&payload),
+        String::new(),
+        "not hex".into(),
+    ];
+    let data = app_state(
+        MockDatabase::new(DbBackend::MySql).into_connection(),
+        Arc::new(provider),
+    );
+
+    for signature in forged {
+        let status = receive(&data.conn, data.payments.as_ref(), &payload, &signature).await;
+        assert!(matches!(status, Err(ServiceError::BadRequest(_))));
+    }
+    assert!(data.conn.into_transaction_log().is_empty());
+}
+
+#[actix_rt::test]
+async fn redelivered_webhook_is_not_booked_twice() {
+    let provider = FakeProvider::new(WEBHOOK_SECRET);
+    let intent_id = intent(&provider).await;
+    let (payload, signature) = provider.intent_succeeded(&intent_id);
+    let conn = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([[stored(WebhookStatus::Processed, &payload)]])
+        .into_connection();
+    let data = app_state(conn, Arc::new(provider));
+
+    let status = receive(&data.conn, data.payments.as_ref(), &payload, &signature).await;
+
+    assert!(matches!(status, Ok(WebhookStatus::Processed)));
+    assert_eq!(statements(&data.conn.into_transaction_log()).len(), 1);
+}
+
+//one move of money in the ledger: both accounts, the stored transaction and the new balance
+fn transfer(db: MockDatabase, before: i64, after: i64, id: i32) -> MockDatabase {
+    db.append_query_results([[account(Some(USER_ID), 0, before)], [account(None, 1, 0)]])
+        .append_query_results([[booked(id, 5, None)]])
+        .append_query_results([[account(Some(USER_ID), 0, after)]])
+        .append_exec_results([inserted(id as u64), inserted(1), inserted(1)])
+}
+
+fn top_up(
+    id: i32,
+    intent_id: &str,
+    amount: i64,
+) -> (wallet_entry::Model, entity::wallet_transaction::Model) {
+    let entry = wallet_entry::Model {
+        id,
+        transaction_id: id,
+        account_id: USER_ID,
+        amount,
+        balance_after: None,
+    };
+    (entry, booked(id, 0, Some(intent_id)))
+}
+
+//refunds are booked and committed before the provider is asked for them,
+//the part it refuses goes back to the wallet
+#[actix_rt::test]
+async fn card_refund_is_booked_before_it_is_sent() {
+    let provider = FakeProvider::new(WEBHOOK_SECRET);
+    let intent_id = intent(&provider).await;
+    let db = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([[account(Some(USER_ID), 0, 3000)]])
+        //newest top-up was paid through the provider, the older one it doesn't know
+        .append_query_results([[top_up(2, &intent_id, 2000), top_up(1, "pi_unknown", 1000)]]);
+    let db = transfer(db, 3000, 1000, 10);
+    //refund sent, the refused one marked as unsent
+    let db = transfer(db, 1000, 0, 11).append_exec_results([inserted(0), inserted(0)]);
+    let conn = transfer(db, 0, 1000, 12)
+        .append_query_results([[account(Some(USER_ID), 0, 1000)]])
+        .into_connection();
+    let data = app_state(conn, Arc::new(provider));
+
+    let refund = refund_to_card(&data.conn, data.payments.as_ref(), USER_ID, None, None)
+        .await
+        .unwrap();
+
+    assert_eq!(refund.refunded, 2000);
+    assert_eq!(refund.balance, 1000);
+    assert_eq!(refund.refunds.len(), 1);
+    let statements = statements(&data.conn.into_transaction_log());
+    let position = |sql: &str| statements.iter().position(|s| s.contains(sql)).unwrap();
+    let sent = position("UPDATE `wallet_transaction`");
+    assert!(position("\"COMMIT\"") < sent, "{:#?}", statements);
+    assert!(statements[sent].contains(&refund.refunds[0].refund_id));
+    assert!(statements[sent].contains("pending-refund:"));
+    assert!(statements[sent + 1..]
+        .iter()
+        .any(|s| s.contains("UPDATE `wallet_transaction`") && s.contains(":unsent")));
+    assert!(statements[sent..]
+        .iter()
+        .any(|s| s.contains("INSERT INTO `wallet_transaction`") && s.contains(":cancelled")));
+}
+
+//database goes away right after the provider took the refund, the refund is still reported
+//and the part that was never sent still goes back to the wallet
+#[actix_rt::test]
+async fn sent_refund_is_reported_when_its_booking_fails() {
+    let provider = FakeProvider::new(WEBHOOK_SECRET);
+    let intent_id = intent(&provider).await;
+    let db = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([[account(Some(USER_ID), 0, 3000)]])
+        .append_query_results([[top_up(2, &intent_id, 2000), top_up(1, "pi_unknown", 1000)]]);
+    let db = transfer(db, 3000, 1000, 10);
+    let db = transfer(db, 1000, 0, 11)
+        .append_exec_errors([DbErr::Exec(RuntimeErr::Internal("connection lost".into()))])
+        .append_exec_results([inserted(0)]);
+    let conn = transfer(db, 0, 1000, 12)
+        .append_query_results([[account(Some(USER_ID), 0, 1000)]])
+        .into_connection();
+    let data = app_state(conn, Arc::new(provider));
+
+    let refund = refund_to_card(&data.conn, data.payments.as_ref(), USER_ID, None, None)
+        .await
+        .unwrap();
+
+    assert_eq!(refund.refunded, 2000);
+    assert_eq!(refund.refunds[0].payment_intent_id, intent_id);
+    let log = data.conn.into_transaction_log();
+    assert!(ran(&log, "INSERT INTO `wallet_transaction`")
+        .iter()
+        .any(|s| s.contains(":cancelled")));
+}
+
+//pending booking as read by the reconciliation, tuples are read by position so the keys
+//only keep the columns in order
+fn pending(external_ref: &str, intent_id: &str, amount: i64) -> BTreeMap<&'static str, Value> {
+    BTreeMap::from([
+        (
+            "a_external_ref",
+            Value::String(Some(Box::new(external_ref.into()))),
+        ),
+        (
+            "b_payment_ref",
+            Value::String(Some(Box::new(intent_id.into()))),
+        ),
+        ("c_user_id", Value::Int(Some(USER_ID))),
+        ("d_amount", Value::BigInt(Some(-amount))),
+    ])
+}
+
+//the request died after booking both refunds, only the first one reached the provider
+#[actix_rt::test]
+async fn pending_refunds_are_reconciled_with_the_provider() {
+    let provider = FakeProvider::new(WEBHOOK_SECRET);
+    let intent_id = intent(&provider).await;
+    let sent = provider
+        .refund(&intent_id, 500, "pending-refund:sent")
+        .await
+        .unwrap();
+    let db = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([[
+            pending("pending-refund:sent", &intent_id, 500),
+            pending("pending-refund:lost", &intent_id, 700),
+        ]])
+        .append_exec_results([inserted(0), inserted(0)]);
+    let conn = transfer(db, 800, 1500, 12).into_connection();
+    let data = app_state(conn, Arc::new(provider));
+
+    reconcile_pending_refunds(&data.conn, data.payments.as_ref())
+        .await
+        .unwrap();
+
+    let log = data.conn.into_transaction_log();
+    let updates = ran(&log, "UPDATE `wallet_transaction`");
+    assert!(
+        updates[0].contains(&sent) && updates[0].contains("pending-refund:sent"),
+        "{:#?}",
+        updates
+    );
+    assert!(
+        updates[1].contains("pending-refund:lost:unsent"),
+        "{:#?}",
+        updates
+    );
+    let given_back = ran(&log, "INSERT INTO `wallet_transaction`");
+    assert_eq!(given_back.len(), 1);
+    assert!(given_back[0].contains("pending-refund:lost:cancelled"));
+    assert!(ran(&log, "INSERT INTO `wallet_entry`")
+        .iter()
+        .any(|s| s.contains("BigInt(Some(700))")));
+}
+
+fn deleted_customer(id: i32, customer_id: &str) -> payment_customer::Model {
+    payment_customer::Model {
+        id,
+        customer_id: customer_id.into(),
+        user_id: USER_ID,
+        created_at: Utc::now(),
+        deleted_at: Some(Utc::now()),
+        provider_deleted_at: None,
+    }
+}
+
+//wallets were deleted but the provider didn't remove their customers, the one it still
+//refuses stays for the next run
+#[actix_rt::test]
+async fn customers_of_deleted_wallets_are_removed_later() {
+    let provider = Arc::new(FakeProvider::new(WEBHOOK_SECRET));
+    intent(&provider).await;
+    let conn = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([[
+            deleted_customer(1, "cus_unknown"),
+            deleted_customer(2, "cus_fake_1"),
+        ]])
+        .append_exec_results([inserted(0)])
+        .into_connection();
+
+    retry_customer_deletions(&conn, provider.as_ref())
+        .await
+        .unwrap();
+
+    assert!(provider.customer("cus_fake_1").await.is_err());
+    let removed = ran(&conn.into_transaction_log(), "UPDATE `payment_customer`");
+    assert_eq!(removed.len(), 1);
+    assert!(removed[0].contains("cus_fake_1"), "{:#?}", removed);
+}
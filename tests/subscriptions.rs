@@ -0,0 +1,76 @@
+mod common;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use chrono::{Datelike, Duration, Local, NaiveTime, Utc};
+use common::*;
+use entity::{menu_info, subscription, subscription_day, subscription_extras};
+use kantyna_api::{payments::FakeProvider, subscriptions::materialise_subscriptions};
+use sea_orm::{DbBackend, DbErr, MockDatabase, MockExecResult, RuntimeErr, Value};
+
+//a db error while ordering gives the day back instead of failing it for good
+#[actix_rt::test]
+async fn temporary_failure_releases_the_day() {
+    let day = Local::now().date_naive() + Duration::days(1);
+    let week_day = day.weekday().num_days_from_monday() as u8;
+    let subscription = subscription::Model {
+        id: 2,
+        user_id: USER_ID,
+        week_days: 1 << week_day,
+        pickup_time: NaiveTime::from_hms_opt(12, 30, 0).unwrap(),
+        with_soup: 0,
+        active: 1,
+        created_at: Utc::now(),
+    };
+    let extras = subscription_extras::Model {
+        id: 1,
+        subscription_id: subscription.id,
+        extras_id: EXTRA_ID,
+    };
+    let claim = subscription_day::Model {
+        id: 4,
+        subscription_id: subscription.id,
+        day,
+        order_id: None,
+        failure: None,
+        created_at: Utc::now(),
+    };
+    let conn = MockDatabase::new(DbBackend::MySql)
+        .append_query_results([[menu_info::Model {
+            id: 1,
+            last_update: Utc::now(),
+        }]])
+        .append_query_results([[BTreeMap::from([(
+            "week_day",
+            Value::TinyUnsigned(Some(week_day)),
+        )])]])
+        .append_query_results([[(subscription, extras)]])
+        .append_query_results([Vec::<subscription_day::Model>::new()])
+        .append_query_results([[pickup_slot(week_day)]])
+        .append_query_results([[claim]])
+        .append_query_errors([DbErr::Query(RuntimeErr::Internal("connection lost".into()))])
+        .append_exec_results([
+            MockExecResult {
+                last_insert_id: 4,
+                rows_affected: 1,
+            },
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            },
+        ])
+        .into_connection();
+    let data = app_state(conn, Arc::new(FakeProvider::new(WEBHOOK_SECRET)));
+
+    materialise_subscriptions(&data).await.unwrap();
+
+    let log = data.conn.into_transaction_log();
+    assert_eq!(
+        ran(&log, "FROM `dinner`").len(),
+        2,
+        "{:#?}",
+        statements(&log)
+    );
+    assert_eq!(ran(&log, "DELETE FROM `subscription_day`").len(), 1);
+    assert!(ran(&log, "UPDATE `subscription_day`").is_empty());
+}